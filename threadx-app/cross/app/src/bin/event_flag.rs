@@ -54,9 +54,9 @@ fn main() -> ! {
                 .initialize(c"pool1", bp_mem)
                 .unwrap();
             //allocate memory for the two tasks.
-            let task1_mem = bp.allocate(256, true).unwrap();
-            let task2_mem = bp.allocate(256, true).unwrap();
-            let task3_mem = bp.allocate(256, true).unwrap();
+            let task1_mem = bp.allocate(256, WaitOption::WaitForever).unwrap();
+            let task2_mem = bp.allocate(256, WaitOption::WaitForever).unwrap();
+            let task3_mem = bp.allocate(256, WaitOption::WaitForever).unwrap();
 
             //TODO: This is too short lived ... types are not correct
             let heap = Aligned([0; 1024]);
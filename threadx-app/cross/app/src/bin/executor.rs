@@ -24,6 +24,7 @@ use threadx_rs::allocator::ThreadXAllocator;
 use threadx_rs::event_flags::EventFlagsGroup;
 use threadx_rs::executor::{self, Executor};
 use threadx_rs::pool::BytePool;
+use threadx_rs::WaitOption;
 
 use threadx_rs::thread::{sleep, Thread};
 
@@ -61,7 +62,7 @@ fn main() -> ! {
             let bp = bp.initialize(c"pool1", bp_mem).unwrap();
 
             //allocate memory for the two tasks.
-            let task2_mem = bp.allocate(1024, true).unwrap();
+            let task2_mem = bp.allocate(1024, WaitOption::WaitForever).unwrap();
 
             let heap: Aligned<[u8; 1024]> = Aligned([0; 1024]);
             let heap_mem = HEAP.init_with(|| heap.0);
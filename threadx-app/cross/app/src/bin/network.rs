@@ -3,7 +3,6 @@
 
 use core::cell::RefCell;
 use core::net::{Ipv4Addr, SocketAddr};
-use core::sync::atomic::AtomicU32;
 use core::time::Duration;
 
 use alloc::boxed::Box;
@@ -16,10 +15,7 @@ use defmt::println;
 use embedded_graphics::mono_font::ascii::FONT_9X18;
 use heapless::String;
 use minimq::broker::IpBroker;
-use minimq::embedded_time::rate::Fraction;
-use minimq::embedded_time::{self, Clock, Instant};
 use minimq::{ConfigBuilder, Minimq};
-use netx_sys::ULONG;
 use static_cell::StaticCell;
 use threadx_app::minimqtransport::MiniMqBasedTransport;
 use threadx_app::network::network::ThreadxTcpWifiNetwork;
@@ -27,17 +23,19 @@ use threadx_app::network::network::ThreadxTcpWifiNetwork;
 use threadx_app::uprotocol_v1::UMessage;
 use threadx_app::utransport::LocalUTransport;
 use threadx_rs::allocator::ThreadXAllocator;
+use threadx_rs::clock::TickClock;
 use threadx_rs::event_flags::GetOption::*;
 use threadx_rs::event_flags::{EventFlagsGroup, EventFlagsGroupHandle};
 
 use threadx_rs::executor::Executor;
 use threadx_rs::mutex::Mutex;
 use threadx_rs::queue::{Queue, QueueReceiver, QueueSender};
-use threadx_rs::thread::{self, sleep};
+use threadx_rs::thread;
+use threadx_rs::time::Ticker;
+use threadx_rs::WaitOption;
 use threadx_rs::WaitOption::*;
 
 use threadx_rs::thread::Thread;
-use threadx_rs::timer::Timer;
 
 use core::fmt::Write;
 
@@ -87,11 +85,11 @@ static MEASURE_THREAD: StaticCell<Thread> = StaticCell::new();
 
 static BOARD: cortex_m::interrupt::Mutex<RefCell<Option<BoardMxAz3166<I2CBus>>>> =
     cortex_m::interrupt::Mutex::new(RefCell::new(None));
-static QUEUE: StaticCell<Queue<Event>> = StaticCell::new();
-static QUEUE_MEM: StaticCell<[u8; 128]> = StaticCell::new();
+static QUEUE: StaticCell<Queue<Event, 32>> = StaticCell::new();
 
 static EVENT_GROUP: StaticCell<EventFlagsGroup> = StaticCell::new();
-static DISPLAY: StaticCell<Mutex<Option<DisplayType<I2CBus>>>> = StaticCell::new();
+static DISPLAY: Mutex<Option<DisplayType<I2CBus>>> =
+    Mutex::with_options(None, Some(c"display_mtx"), false);
 
 #[cortex_m_rt::entry]
 fn main() -> ! {
@@ -110,16 +108,21 @@ fn main() -> ! {
 
             GLOBAL.initialize(heap_mem).unwrap();
 
+            // Report the stack usage of all threads when one of them overflows
+            thread::set_stack_error_handler(|_thread| {
+                defmt::error!("Thread stack overflow detected");
+                thread::log_stack_usage();
+            })
+            .unwrap();
+
             // Get the peripherals
-            let display_ref = DISPLAY.init(Mutex::new(None));
-            let _ = display_ref.initialize(c"display_mtx", false).unwrap();
             let display = interrupt::free(|cs| {
                 let mut board = BOARD.borrow(cs).borrow_mut();
                 board.as_mut().unwrap().display.take().unwrap()
             });
             {
                 // Temporary scope to hold the lock
-                let mut display_guard = display_ref.lock(WaitForever).unwrap();
+                let mut display_guard = DISPLAY.lock(WaitForever).unwrap();
                 display_guard.replace(display);
             }
             let (hts211, i2c) = interrupt::free(|cs| {
@@ -132,9 +135,8 @@ fn main() -> ! {
             });
 
             // Create communication queue
-            let qm = QUEUE_MEM.init_with(|| [0u8; 128]);
             let queue = QUEUE.init(Queue::new());
-            let (sender, receiver) = queue.initialize(c"m_queue", qm).unwrap();
+            let (sender, receiver) = queue.initialize_owned(c"m_queue").unwrap();
 
             // create events flag group
             let event_group = EVENT_GROUP.init(EventFlagsGroup::new());
@@ -146,7 +148,7 @@ fn main() -> ! {
             let _ = wifi_thread
                 .initialize_with_autostart_box(
                     "wifi_thread",
-                    Box::new(move || do_network(receiver, evt_handle, display_ref)),
+                    Box::new(move || do_network(receiver, evt_handle, &DISPLAY)),
                     wifi_thread_stack,
                     4,
                     4,
@@ -192,52 +194,17 @@ fn do_measurement(
         )
         .unwrap();
     println!("WLAN connected, beginning to measure");
+    let mut ticker = Ticker::new(Duration::from_secs(5));
     loop {
         let deg = hts221.temperature_x8(&mut i2c).unwrap() as i32;
         let _ = snd.send(Event::TemperatureMeasurement(deg), WaitForever);
         println!("Current temperature: {}", deg);
-        let _ = sleep(Duration::from_secs(5));
-    }
-}
-
-fn start_clock() -> impl Clock {
-    static TICKS: AtomicU32 = AtomicU32::new(0);
-
-    // TODO: Hardware Clock implementation
-    struct ThreadXSecondClock {}
-
-    impl embedded_time::Clock for ThreadXSecondClock {
-        type T = u32;
-
-        const SCALING_FACTOR: embedded_time::rate::Fraction = Fraction::new(1, 1);
-
-        fn try_now(&self) -> Result<embedded_time::Instant<Self>, embedded_time::clock::Error> {
-            Ok(Instant::new(
-                TICKS.fetch_add(0, core::sync::atomic::Ordering::Relaxed),
-            ))
+        if let Ok(missed) = ticker.wait() {
+            if missed > 0 {
+                defmt::warn!("Measurement overran, missed {} period starts", missed);
+            }
         }
     }
-
-    extern "C" fn clock_tick(_arg: ULONG) {
-        TICKS.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
-    }
-
-    // Start the clock timer --> Should be done in Hardware but we do it via ThreadX for the fun of it
-
-    static CLOCK_TIMER: StaticCell<Timer> = StaticCell::new();
-    let clock_timer = CLOCK_TIMER.init(Timer::new());
-
-    let _ = clock_timer
-        .initialize_with_fn(
-            c"clock_timer_mqtt",
-            Some(clock_tick),
-            0,
-            Duration::from_secs(1),
-            Duration::from_secs(1),
-            true,
-        )
-        .unwrap();
-    ThreadXSecondClock {}
 }
 
 fn print_text(text: &str, display: &mut DisplayType<I2CBus>) {
@@ -256,7 +223,7 @@ fn print_text(text: &str, display: &mut DisplayType<I2CBus>) {
 pub fn do_network(
     recv: QueueReceiver<Event>,
     evt_handle: EventFlagsGroupHandle,
-    display: &Mutex<Option<DisplayType<I2CBus>>>,
+    display: &'static Mutex<Option<DisplayType<I2CBus>>>,
 ) -> ! {
     defmt::println!("Initializing Network");
     // Initialize the globlal async executor
@@ -278,7 +245,7 @@ pub fn do_network(
         .unwrap();
 
     print_text("WLAN(x)\nMQTT()", &mut display);
-    let clock = start_clock();
+    let clock = TickClock::<1>::new();
     let mut transport = MiniMqBasedTransport::new(Minimq::new(network, clock, mqtt_cfg));
     // Signal that measurements can begin
     let _res = evt_handle
@@ -286,11 +253,12 @@ pub fn do_network(
         .unwrap();
 
     loop {
-        // Need to poll the transport in order to keep it connected
+        // Need to poll the transport at least every second in order to keep it connected
         transport.poll();
         if transport.is_connected() {
             print_text("WLAN(x)\nMQTT(x)", &mut display);
-            if let Ok(evt) = recv.receive(NoWait) {
+            // Wait for the next measurement, but not longer than until the next transport poll
+            if let Ok(evt) = recv.receive(WaitOption::timeout(Duration::from_secs(1))) {
                 // TODO: Use upRust to do it all properly. This creates a very simple (valid) uMessage MQTT payload.
                 // Create a umessage and then call send and block_on from the
                 let mut umessage = UMessage::default();
@@ -300,8 +268,7 @@ pub fn do_network(
             }
         } else {
             print_text("WLAN(x)\nMQTT()", &mut display);
+            let _ = thread::sleep(Duration::from_millis(1000)).unwrap();
         }
-        // Poll every 1000ms
-        let _ = thread::sleep(Duration::from_millis(1000)).unwrap();
     }
 }
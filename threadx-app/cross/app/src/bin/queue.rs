@@ -7,6 +7,7 @@ use board::{BoardMxAz3166, LowLevelInit};
 use defmt::println;
 use static_cell::StaticCell;
 use threadx_rs::allocator::ThreadXAllocator;
+use threadx_rs::executor::Executor;
 
 use threadx_rs::pool::BytePool;
 use threadx_rs::queue::Queue;
@@ -50,9 +51,9 @@ fn main() -> ! {
                 .initialize(c"pool1", bp_mem)
                 .unwrap();
             //allocate memory for the two tasks.
-            let task1_mem = bp.allocate(256, true).unwrap();
-            let task2_mem = bp.allocate(256, true).unwrap();
-            let queue_mem = bp.allocate(64, true).unwrap();
+            let task1_mem = bp.allocate(256, WaitOption::WaitForever).unwrap();
+            let task2_mem = bp.allocate(512, WaitOption::WaitForever).unwrap();
+            let queue_mem = bp.allocate(64, WaitOption::WaitForever).unwrap();
             let queue = QUEUE.init(Queue::new());
             let (sender, receiver) = queue
                 .initialize(
@@ -79,8 +80,10 @@ fn main() -> ! {
                 .initialize_with_autostart_box("thread1", Box::new(thread1_func), task1_mem.consume(), 1, 1, 0)
                 .unwrap();
 
+            let executor = Executor::new();
             let thread2_fn = move || loop {
-                let msg = receiver.receive(WaitOption::WaitForever).unwrap();
+                // The send notification of the queue wakes the executor
+                let msg = executor.block_on(receiver.receive_async()).unwrap();
                 match msg {
                     Event::Event => {
                         println!("Thread 2: RX Event");
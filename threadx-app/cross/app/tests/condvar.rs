@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+
+// A notification must wake the thread which was waiting when it was sent, even if a higher
+// priority thread starts waiting before the notified thread blocked.
+
+use core::time::Duration;
+
+use board::{BoardMxAz3166, LowLevelInit};
+use defmt::println;
+use static_cell::StaticCell;
+use threadx_app as _; // memory layout + panic handler
+use threadx_rs::allocator::ThreadXAllocator;
+use threadx_rs::condvar::Condvar;
+use threadx_rs::mutex::Mutex;
+use threadx_rs::thread::{self, sleep, Thread};
+use threadx_rs::WaitOption;
+
+extern crate alloc;
+
+#[global_allocator]
+static GLOBAL: ThreadXAllocator = ThreadXAllocator::new();
+
+static HEAP: StaticCell<[u8; 1024]> = StaticCell::new();
+static CONTROL: StaticCell<Thread> = StaticCell::new();
+static EARLY: StaticCell<Thread> = StaticCell::new();
+static LATE: StaticCell<Thread> = StaticCell::new();
+static CONTROL_STACK: StaticCell<[u8; 2048]> = StaticCell::new();
+static EARLY_STACK: StaticCell<[u8; 1024]> = StaticCell::new();
+static LATE_STACK: StaticCell<[u8; 1024]> = StaticCell::new();
+
+static MUTEX: Mutex<()> = Mutex::new(());
+static CONDVAR: Condvar = Condvar::new();
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let tx = threadx_rs::Builder::new(
+        |ticks_per_second| {
+            BoardMxAz3166::low_level_init(ticks_per_second).unwrap();
+        },
+        |_mem_start| {
+            GLOBAL.initialize(HEAP.init([0u8; 1024])).unwrap();
+            thread::Builder::new()
+                .name("control")
+                .priority(10)
+                .stack(CONTROL_STACK.init([0u8; 2048]))
+                .spawn(CONTROL.init(Thread::new()), late_waiter_races_notify_one)
+                .unwrap();
+        },
+    );
+
+    tx.initialize();
+    threadx_app::exit()
+}
+
+fn late_waiter_races_notify_one() {
+    let early = thread::Builder::new()
+        .name("early")
+        .priority(20)
+        .stack(EARLY_STACK.init([0u8; 1024]))
+        .spawn_joinable(EARLY.init(Thread::new()), || {
+            let guard = MUTEX.lock(WaitOption::WaitForever).unwrap();
+            // Hold the mutex until the control thread waits for it. Releasing it in `wait_timeout`
+            // then switches to the control thread before this thread blocks on the condvar.
+            sleep(Duration::from_millis(100)).unwrap();
+            let (_guard, result) = CONDVAR.wait_timeout(guard, Duration::from_secs(1)).unwrap();
+            result.timed_out()
+        })
+        .unwrap();
+    sleep(Duration::from_millis(50)).unwrap();
+
+    let guard = MUTEX.lock(WaitOption::WaitForever).unwrap();
+    // The early thread is registered but not blocked yet
+    CONDVAR.notify_one();
+    drop(guard);
+
+    // Runs right away and waits before the early thread gets to block
+    let late = thread::Builder::new()
+        .name("late")
+        .priority(5)
+        .stack(LATE_STACK.init([0u8; 1024]))
+        .spawn_joinable(LATE.init(Thread::new()), || {
+            let guard = MUTEX.lock(WaitOption::WaitForever).unwrap();
+            let (_guard, result) = CONDVAR.wait_timeout(guard, Duration::from_millis(200)).unwrap();
+            result.timed_out()
+        })
+        .unwrap();
+
+    let early_timed_out = early.join().unwrap();
+    let late_timed_out = late.join().unwrap();
+    defmt::assert!(!early_timed_out, "notification for the early waiter was lost");
+    defmt::assert!(late_timed_out, "late waiter took a notification sent before it waited");
+    println!("condvar: late waiter racing notify_one passed");
+    threadx_app::exit()
+}
@@ -7,10 +7,68 @@ use threadx_app as _; // memory layout + panic handler
 // feature)
 #[defmt_test::tests]
 mod tests {
+    use core::cmp::Ordering;
+    use core::time::Duration;
+
     use defmt::assert;
+    use threadx_rs::time::{Instant, Rounding, TxTicks};
 
     #[test]
     fn it_works() {
         assert!(true)
     }
+
+    #[test]
+    fn rounds_partial_ticks() {
+        let tick = TxTicks::new(1).to_duration();
+        let half = tick / 2;
+        assert!(TxTicks::checked_from_duration(half, Rounding::Up) == Some(TxTicks::new(1)));
+        assert!(TxTicks::checked_from_duration(half, Rounding::Down) == Some(TxTicks::ZERO));
+        assert!(TxTicks::checked_from_duration(tick, Rounding::Up) == Some(TxTicks::new(1)));
+        assert!(TxTicks::checked_from_duration(tick, Rounding::Down) == Some(TxTicks::new(1)));
+        assert!(TxTicks::checked_from_duration(Duration::ZERO, Rounding::Up) == Some(TxTicks::ZERO));
+        assert!(TxTicks::from(Duration::from_nanos(1)) == TxTicks::new(1));
+    }
+
+    #[test]
+    fn overflow_boundary() {
+        let max = TxTicks::MAX.to_duration();
+        assert!(TxTicks::checked_from_duration(max, Rounding::Up) == Some(TxTicks::MAX));
+        let above = max + TxTicks::new(1).to_duration();
+        assert!(TxTicks::checked_from_duration(above, Rounding::Up).is_none());
+        assert!(TxTicks::saturating_from_duration(above, Rounding::Up) == TxTicks::MAX);
+        let just_above = max + Duration::from_nanos(1);
+        assert!(TxTicks::checked_from_duration(just_above, Rounding::Down) == Some(TxTicks::MAX));
+        assert!(TxTicks::checked_from_duration(Duration::MAX, Rounding::Down).is_none());
+        assert!(TxTicks::from(Duration::MAX) == TxTicks::MAX);
+    }
+
+    #[test]
+    fn instants_across_wrap() {
+        let before = Instant::from_ticks(u32::MAX - 4);
+        let after = before.wrapping_add_ticks(10);
+        assert!(after.ticks() == 5);
+        assert!(after.ticks_since(before) == 10);
+        assert!(before.ticks_since(after) == -10);
+        assert!(after > before);
+        assert!(before < after);
+        assert!(after.partial_cmp(&after) == Some(Ordering::Equal));
+        assert!(after.checked_duration_since(before) == Some(TxTicks::new(10).to_duration()));
+        assert!(before.checked_duration_since(after).is_none());
+        assert!(before.duration_since(after) == Duration::ZERO);
+        assert!(before + TxTicks::new(10).to_duration() == after);
+    }
+
+    #[test]
+    fn instants_half_range_apart() {
+        let start = Instant::from_ticks(0);
+        let farthest = start.wrapping_add_ticks(i32::MAX as u32);
+        assert!(farthest > start);
+        // Beyond half the counter range the order flips
+        let beyond = farthest.wrapping_add_ticks(1);
+        assert!(beyond < start);
+        let half = TxTicks::new(i32::MAX as u32 + 1).to_duration();
+        assert!(start.checked_add(half).is_none());
+        assert!(start.checked_sub(half).is_none());
+    }
 }
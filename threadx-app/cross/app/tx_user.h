@@ -1 +1,2 @@
 #define TX_TIMER_THREAD_STACK_SIZE 2048
+#define TX_ENABLE_STACK_CHECKING
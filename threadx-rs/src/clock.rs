@@ -0,0 +1,96 @@
+//! Clocks and delays backed by the kernel tick counter for use with the
+//! `embedded-hal`, `embedded-time` and `fugit` ecosystems. Enable the
+//! feature of the same name to get the respective implementation.
+
+use core::time::Duration;
+
+use crate::thread::sleep_ticks;
+use crate::time::{extended_ticks, TxTicks, TICKS_PER_SECOND};
+
+/// Clock reading the ThreadX tick counter with a resolution of `HZ` counts
+/// per second, the kernel tick rate by default. Counts are derived from the
+/// 64 bit tick count of `time::extended_ticks` so 32 bit readings wrap
+/// cleanly regardless of the resolution.
+///
+/// ```ignore
+/// // Millisecond clock for a protocol stack
+/// let clock = TickClock::<1000>::new();
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TickClock<const HZ: u32 = TICKS_PER_SECOND>;
+
+impl<const HZ: u32> TickClock<HZ> {
+    pub const fn new() -> Self {
+        TickClock
+    }
+
+    /// Time since the kernel started in units of `1 / HZ` seconds.
+    pub fn now_u64(&self) -> u64 {
+        (extended_ticks() as u128 * HZ as u128 / TICKS_PER_SECOND as u128) as u64
+    }
+
+    /// Like `now_u64` but wraps around after 2^32 counts.
+    pub fn now_u32(&self) -> u32 {
+        self.now_u64() as u32
+    }
+}
+
+#[cfg(feature = "fugit")]
+impl<const HZ: u32> TickClock<HZ> {
+    pub fn instant(&self) -> fugit::TimerInstantU64<HZ> {
+        fugit::TimerInstantU64::from_ticks(self.now_u64())
+    }
+
+    pub fn instant_u32(&self) -> fugit::TimerInstantU32<HZ> {
+        fugit::TimerInstantU32::from_ticks(self.now_u32())
+    }
+}
+
+#[cfg(feature = "embedded-time")]
+impl<const HZ: u32> embedded_time::Clock for TickClock<HZ> {
+    type T = u32;
+
+    const SCALING_FACTOR: embedded_time::rate::Fraction =
+        embedded_time::rate::Fraction::new(1, HZ);
+
+    fn try_now(&self) -> Result<embedded_time::Instant<Self>, embedded_time::clock::Error> {
+        Ok(embedded_time::Instant::new(self.now_u32()))
+    }
+}
+
+/// Delay that puts the calling thread to sleep, so other threads run in the
+/// meantime. Delays are rounded up to whole kernel ticks plus one, since the
+/// current tick is already partly over, so they never end early. Use a
+/// hardware timer where shorter delays matter.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Delay;
+
+impl Delay {
+    pub const fn new() -> Self {
+        Delay
+    }
+
+    /// Sleep for at least `d`.
+    pub fn delay(&mut self, d: Duration) {
+        if d.is_zero() {
+            return;
+        }
+        let ticks = TxTicks::from(d).ticks().saturating_add(1);
+        let _ = sleep_ticks(TxTicks::new(ticks));
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::delay::DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.delay(Duration::from_nanos(ns as u64));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.delay(Duration::from_micros(us as u64));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay(Duration::from_millis(ms as u64));
+    }
+}
@@ -0,0 +1,255 @@
+use core::cell::UnsafeCell;
+use core::ffi::CStr;
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+use core::time::Duration;
+
+use crate::mutex::{LockResult, MutexError, MutexGuard, RawMutex};
+use crate::{tx_checked_call, tx_checked_call_no_log};
+
+use super::error::TxError;
+use super::WaitOption;
+use num_traits::FromPrimitive;
+use threadx_sys::{
+    _tx_semaphore_create, _tx_semaphore_delete, _tx_semaphore_get, _tx_semaphore_put,
+    _tx_thread_identify, CHAR, TX_SEMAPHORE, UINT,
+};
+
+/// Condition variable to be used together with a `Mutex`.
+///
+/// Each waiting thread blocks on its own ThreadX semaphore, created for the duration of the wait, so
+/// a notification can only wake threads which were already waiting when it was sent. Notifications
+/// wake the waiters in the order of their priority at the time they started waiting. Like the mutex
+/// the condition variable is const constructible and creates its ThreadX mutex on first use. All
+/// functions must be called from thread context.
+///
+/// ```ignore
+/// static READY: Mutex<bool> = Mutex::new(false);
+/// static CONDVAR: Condvar = Condvar::new();
+///
+/// let guard = READY.lock(WaitOption::WaitForever).unwrap();
+/// let _guard = CONDVAR.wait_while(guard, |ready| !*ready).unwrap();
+/// ```
+pub struct Condvar {
+    name: Option<&'static CStr>,
+    // Protects `waiters`
+    lock: RawMutex,
+    // Threads which registered for waiting and were not notified yet, highest priority first
+    waiters: UnsafeCell<*mut Waiter>,
+}
+
+/// Safety: `waiters` is only accessed while holding `lock`.
+unsafe impl Send for Condvar {}
+unsafe impl Sync for Condvar {}
+
+/// A thread blocked in `Condvar::block`. Lives on the stack of the waiting thread and is only
+/// accessed through raw pointers while linked into the waiter list.
+struct Waiter {
+    semaphore: MaybeUninit<TX_SEMAPHORE>,
+    priority: UINT,
+    next: *mut Waiter,
+    // Set when a notification took the waiter off the list, its semaphore put follows
+    notified: bool,
+}
+
+/// Whether `Condvar::wait_timeout` returned because the timeout expired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+impl Condvar {
+    pub const fn new() -> Condvar {
+        Condvar::with_name(None)
+    }
+
+    /// Creates a condition variable with a name shown by debuggers.
+    pub const fn with_name(name: Option<&'static CStr>) -> Condvar {
+        Condvar {
+            name,
+            lock: RawMutex::new(None, true),
+            waiters: UnsafeCell::new(core::ptr::null_mut()),
+        }
+    }
+
+    /// Runs `f` with the waiter list locked.
+    fn with_waiters<R>(&'static self, f: impl FnOnce(&mut *mut Waiter) -> R) -> Result<R, TxError> {
+        while let Err(e) = self.lock.acquire(WaitOption::WaitForever) {
+            // Retry if someone called `tx_thread_wait_abort` on us
+            if !matches!(e, TxError::WaitAborted) {
+                return Err(e);
+            }
+        }
+        // Safety: `waiters` is protected by `lock`.
+        let result = f(unsafe { &mut *self.waiters.get() });
+        self.lock.put();
+        Ok(result)
+    }
+
+    /// Unlocks the mutex, blocks until notified or `wait_option` expired and locks the mutex again.
+    /// Returns true as second value if the wait timed out.
+    fn block<T>(
+        &'static self,
+        guard: MutexGuard<'static, T>,
+        wait_option: WaitOption,
+    ) -> (LockResult<MutexGuard<'static, T>>, bool) {
+        let mut node = Waiter {
+            semaphore: MaybeUninit::uninit(),
+            // Safety: Called from thread context, so there is a current thread.
+            priority: unsafe { (*_tx_thread_identify()).tx_thread_priority },
+            next: core::ptr::null_mut(),
+            notified: false,
+        };
+        // The waiter does not move until its semaphore is deleted below
+        let waiter: *mut Waiter = &mut node;
+        let sem_ptr: *mut TX_SEMAPHORE = unsafe { addr_of_mut!((*waiter).semaphore) }.cast();
+        let name = self.name.map_or(core::ptr::null_mut(), |n| n.as_ptr() as *mut CHAR);
+        if let Err(e) = tx_checked_call!(_tx_semaphore_create(sem_ptr, name, 0)) {
+            return (Err(MutexError::MutexError(e)), false);
+        }
+        // Register before the mutex is released so no notification gets lost
+        if let Err(e) = self.with_waiters(|waiters| unsafe { insert(waiters, waiter) }) {
+            let _ = tx_checked_call!(_tx_semaphore_delete(sem_ptr));
+            return (Err(MutexError::MutexError(e)), false);
+        }
+        let mutex = guard.unlock();
+        let mut notified =
+            tx_checked_call_no_log!(_tx_semaphore_get(sem_ptr, wait_option.into())).is_ok();
+        if !notified {
+            // Timed out or aborted. Deregister, unless a notification took us off the list in the
+            // meantime. Its put is then awaited, since the notifier still uses the waiter.
+            let removed = self.with_waiters(|waiters| unsafe {
+                !(*waiter).notified && remove(waiters, waiter)
+            });
+            if !matches!(removed, Ok(true)) {
+                let wait_forever = WaitOption::WaitForever.into();
+                while tx_checked_call_no_log!(_tx_semaphore_get(sem_ptr, wait_forever)).is_err() {}
+                notified = true;
+            }
+        }
+        let _ = tx_checked_call!(_tx_semaphore_delete(sem_ptr));
+        (mutex.lock(WaitOption::WaitForever), !notified)
+    }
+
+    /// Blocks the current thread until notified. Spurious wakeups are possible.
+    pub fn wait<T>(
+        &'static self,
+        guard: MutexGuard<'static, T>,
+    ) -> LockResult<MutexGuard<'static, T>> {
+        self.block(guard, WaitOption::WaitForever).0
+    }
+
+    /// Blocks the current thread as long as `condition` returns true.
+    pub fn wait_while<T, F>(
+        &'static self,
+        mut guard: MutexGuard<'static, T>,
+        mut condition: F,
+    ) -> LockResult<MutexGuard<'static, T>>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard)?;
+        }
+        Ok(guard)
+    }
+
+    /// Like `wait` but gives up after `timeout`.
+    pub fn wait_timeout<T>(
+        &'static self,
+        guard: MutexGuard<'static, T>,
+        timeout: Duration,
+    ) -> LockResult<(MutexGuard<'static, T>, WaitTimeoutResult)> {
+        let (result, timed_out) = self.block(guard, WaitOption::timeout(timeout));
+        let timed_out = WaitTimeoutResult(timed_out);
+        match result {
+            Ok(guard) => Ok((guard, timed_out)),
+            Err(e) => Err(e.map_guard(|guard| (guard, timed_out))),
+        }
+    }
+
+    /// Wakes up the highest priority waiting thread.
+    pub fn notify_one(&'static self) {
+        self.notify(1);
+    }
+
+    /// Wakes up all waiting threads, highest priority first.
+    pub fn notify_all(&'static self) {
+        self.notify(u32::MAX);
+    }
+
+    fn notify(&'static self, max: u32) {
+        // Take the waiters off the list while locked but wake them afterwards
+        let Ok(mut woken) = self.with_waiters(|waiters| unsafe { take(waiters, max) }) else {
+            return;
+        };
+        while !woken.is_null() {
+            // Safety: A notified waiter stays alive until its semaphore was put, so `next` is read
+            // before.
+            let (next, semaphore) = unsafe { ((*woken).next, addr_of_mut!((*woken).semaphore)) };
+            let _ = tx_checked_call!(_tx_semaphore_put(semaphore.cast()));
+            woken = next;
+        }
+    }
+}
+
+/// Links `waiter` behind all waiters of the same or higher priority.
+///
+/// Safety: The list must be locked and all waiters in it alive.
+unsafe fn insert(waiters: &mut *mut Waiter, waiter: *mut Waiter) {
+    let mut link: *mut *mut Waiter = waiters;
+    // Lower numbers are higher priorities
+    while !(*link).is_null() && (**link).priority <= (*waiter).priority {
+        link = addr_of_mut!((**link).next);
+    }
+    (*waiter).next = *link;
+    *link = waiter;
+}
+
+/// Unlinks `waiter`, returns false if it is not in the list.
+///
+/// Safety: The list must be locked and all waiters in it alive.
+unsafe fn remove(waiters: &mut *mut Waiter, waiter: *mut Waiter) -> bool {
+    let mut link: *mut *mut Waiter = waiters;
+    while !(*link).is_null() {
+        if *link == waiter {
+            *link = (*waiter).next;
+            return true;
+        }
+        link = addr_of_mut!((**link).next);
+    }
+    false
+}
+
+/// Unlinks up to `max` waiters from the front and marks them notified. Returns them as a list of
+/// their own.
+///
+/// Safety: The list must be locked and all waiters in it alive.
+unsafe fn take(waiters: &mut *mut Waiter, max: u32) -> *mut Waiter {
+    let first = *waiters;
+    let mut last: *mut Waiter = core::ptr::null_mut();
+    let mut rest = first;
+    let mut count = 0;
+    while count < max && !rest.is_null() {
+        (*rest).notified = true;
+        last = rest;
+        rest = (*rest).next;
+        count += 1;
+    }
+    if last.is_null() {
+        return core::ptr::null_mut();
+    }
+    (*last).next = core::ptr::null_mut();
+    *waiters = rest;
+    first
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Condvar::new()
+    }
+}
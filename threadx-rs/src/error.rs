@@ -43,5 +43,9 @@ pub enum TxError {
     CeilingExceeded = threadx_sys::TX_CEILING_EXCEEDED,
     InvalidCeiling = threadx_sys::TX_INVALID_CEILING,
     FeatureNotEnabled = threadx_sys::TX_FEATURE_NOT_ENABLED,
+    // Errors detected by threadx-rs before calling into ThreadX
+    /// A bounded wait expired before the resource became available.
+    Timeout = 0xFC,
+    NameTooLong = 0xFD,
     Unknown = 0xFE,
 }
\ No newline at end of file
@@ -3,11 +3,11 @@ use core::mem::MaybeUninit;
 
 use threadx_sys::{_tx_event_flags_create, TX_EVENT_FLAGS_GROUP};
 use threadx_sys::{
-    _tx_event_flags_get, _tx_event_flags_set,
+    _tx_event_flags_get, _tx_event_flags_set, _tx_event_flags_set_notify,
     ULONG,
 };
 
-use crate::tx_checked_call;
+use crate::{tx_checked_call, NotifyFn, NotifySlot};
 
 use super::error::TxError;
 use super::WaitOption;
@@ -31,8 +31,11 @@ pub enum SetOption {
     SetAny = threadx_sys::TX_OR,
 }
 
+/// The control block is the first field so the Rust side data can be found from the pointer ThreadX passes to callbacks.
+#[repr(C)]
 pub struct EventFlagsGroup {
     flag_group: MaybeUninit<TX_EVENT_FLAGS_GROUP>,
+    publish_notify: NotifySlot<EventFlagsGroupHandle>,
 }
 #[derive(Copy, Clone)]
 
@@ -50,6 +53,7 @@ impl EventFlagsGroup {
     pub const fn new() -> EventFlagsGroup {
         EventFlagsGroup {
             flag_group: core::mem::MaybeUninit::uninit(),
+            publish_notify: NotifySlot::new(),
         }
     }
 }
@@ -71,6 +75,15 @@ impl EventFlagsGroupHandle {
         tx_checked_call!(_tx_event_flags_set(self.flag_group_ptr, flags_to_set, 0))
     }
 
+    /// Clear `flags_to_clear` and leave the other flags as they are.
+    pub fn clear(&self, flags_to_clear: u32) -> Result<(), TxError> {
+        tx_checked_call!(_tx_event_flags_set(
+            self.flag_group_ptr,
+            !flags_to_clear,
+            SetOption::SetAndClear as ULONG
+        ))
+    }
+
     pub fn get(
         &self,
         requested_flags: u32,
@@ -83,8 +96,31 @@ impl EventFlagsGroupHandle {
             requested_flags,
             get_option as ULONG,
             &mut actual_flags,
-            wait_option as ULONG
-        ))?;
+            wait_option.into()
+        ))
+        .map_err(|e| wait_option.timed_out(e))?;
         Ok(actual_flags)
     }
+
+    /// Register a closure called each time flags are published, `None` removes it. The closure runs in the
+    /// context of the publisher, possibly an ISR, see `NotifyFn`. ThreadX must be built without
+    /// `TX_DISABLE_NOTIFY_CALLBACKS`, otherwise `TxError::FeatureNotEnabled` is returned.
+    pub fn set_publish_notify(&self, notify: Option<NotifyFn<EventFlagsGroupHandle>>) -> Result<(), TxError> {
+        // Safety: Handles are only created by `EventFlagsGroup::initialize` so the control block is embedded in an
+        // `EventFlagsGroup`.
+        let group = unsafe { &*(self.flag_group_ptr as *const EventFlagsGroup) };
+        group.publish_notify.set(notify);
+        tx_checked_call!(_tx_event_flags_set_notify(
+            self.flag_group_ptr,
+            notify.map(|_| event_flags_set_notify_trampoline as unsafe extern "C" fn(*mut TX_EVENT_FLAGS_GROUP))
+        ))
+    }
+}
+
+unsafe extern "C" fn event_flags_set_notify_trampoline(group_ptr: *mut TX_EVENT_FLAGS_GROUP) {
+    // Safety: The notification is only registered for groups embedded in an `EventFlagsGroup`.
+    let group = &*(group_ptr as *const EventFlagsGroup);
+    group.publish_notify.call(EventFlagsGroupHandle {
+        flag_group_ptr: group_ptr,
+    });
 }
@@ -28,14 +28,12 @@ DEALINGS IN THE SOFTWARE.
 
 use core::{
     future::{Future, IntoFuture},
-    mem::MaybeUninit,
-    sync::atomic::AtomicBool,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
     task::{Context, Poll, Waker},
 };
 
-use crate::{event_flags::EventFlagsGroup, mutex::StaticMutex, WaitOption::WaitForever};
+use crate::{event_flags::EventFlagsGroup, event_flags::GetOption, WaitOption::WaitForever};
 use static_cell::StaticCell;
-use threadx_sys::TX_MUTEX_STRUCT;
 
 use crate::event_flags::EventFlagsGroupHandle;
 extern crate alloc;
@@ -44,80 +42,36 @@ extern crate alloc;
  * A port of the main parts of the pollster library using ThreadX components.
  */
 
-#[derive(Clone, Copy, PartialEq)]
-enum SignalState {
-    Unused,
-    Empty,
-    Waiting,
-    Notified,
-}
-
 static EXECUTOR_EVENT: StaticCell<EventFlagsGroup> = StaticCell::new();
-static mut MUTEX_S: TX_MUTEX_STRUCT = unsafe { MaybeUninit::zeroed().assume_init() };
-static SIGNALS: StaticMutex<[SignalState; 31]> =
-    StaticMutex::new([SignalState::Unused; 31], &raw mut MUTEX_S);
+// One bit per `block_on` call in progress, the index of the bit selects the event flag of its signal
+static SIGNALS_IN_USE: AtomicU32 = AtomicU32::new(0);
 
+/// Wakes the thread blocked in `block_on` through its event flag. Setting event flags is allowed from
+/// interrupt handlers and timers, so the waker may be woken from any context.
 struct Signal {
-    state_index: usize,
+    flag: u32,
     event_flag_handle: EventFlagsGroupHandle,
 }
 static EXECUTOR_INITIALIZED: AtomicBool = AtomicBool::new(false);
-// TODO: Does not work for more then one signal. Should work for 32 (number of event_flags))
+
 impl Signal {
-    fn new(event_flag_handle: EventFlagsGroupHandle, index: usize) -> Self {
+    fn new(event_flag_handle: EventFlagsGroupHandle, index: u32) -> Self {
         Self {
-            state_index: index,
-            event_flag_handle: event_flag_handle,
+            flag: 1 << index,
+            event_flag_handle,
         }
     }
 
+    /// Blocks until `notify` was called since the last wait. A notification arriving before the wait
+    /// leaves the flag set, so it is not lost.
     fn wait(&self) {
-        let mut binding = SIGNALS.lock(WaitForever).unwrap();
-        let state = binding.get_mut(self.state_index).unwrap();
-        match *state {
-            SignalState::Notified => *state = SignalState::Empty,
-            SignalState::Waiting => {
-                unreachable!("Multiple threads waiting on the same signal: Open a bug report!");
-            }
-            SignalState::Empty => {
-                // Nothing has happened yet, and we're the only thread waiting (as should be the case!). Set the state
-                // accordingly and begin polling the condvar in a loop until it's no longer telling us to wait. The
-                // loop prevents incorrect spurious wakeups.
-                *state = SignalState::Waiting;
-                // Release the mutex.
-                // Wait for notification. TODO: What happens if we were preempted in between? Wait can only be called by
-                // the executor routine when the future was PENDING. What might happen is that between the Mutex drop and the
-                // waiting for the event flag a notification came in ie.
-                // MUTEX_UNLOCK --> Preempt Executor Thread --> notify_called on different thread.
-                // ThreadX seems to guarantee no spurious wakeups so we can assume that this thread is only woken up after
-                // the request flag is SIGNALED_X
-                let requested_flag = 0b1 << self.state_index;
-                drop(binding);
-                self.event_flag_handle
-                    .get(
-                        requested_flag,
-                        crate::event_flags::GetOption::WaitAllAndClear,
-                        WaitForever,
-                    )
-                    .unwrap();
-            }
-            SignalState::Unused => todo!(),
-        }
+        self.event_flag_handle
+            .get(self.flag, GetOption::WaitAnyAndClear, WaitForever)
+            .unwrap();
     }
 
     fn notify(&self) {
-        let mut binding = SIGNALS.lock(WaitForever).unwrap();
-        let state = binding.get_mut(self.state_index).unwrap();
-
-        match *state {
-            SignalState::Notified => {}
-            SignalState::Empty => *state = SignalState::Notified,
-            SignalState::Waiting => {
-                *state = SignalState::Empty;
-                self.event_flag_handle.publish(0x1).unwrap()
-            }
-            SignalState::Unused => todo!(),
-        }
+        let _ = self.event_flag_handle.publish(self.flag);
     }
 }
 
@@ -138,15 +92,14 @@ pub struct Executor {
 impl Executor {
     pub fn new() -> Self {
         // Initialize the mutex on first call
-        if EXECUTOR_INITIALIZED.load(core::sync::atomic::Ordering::Acquire) {
+        if EXECUTOR_INITIALIZED.load(Ordering::Acquire) {
             panic!("Executor initialized twice");
         };
 
-        SIGNALS.initialize(c"signal_mtx", false).unwrap();
         let evt = EXECUTOR_EVENT.init(EventFlagsGroup::new());
         let executor_event_handle = evt.initialize(c"ExecutorGroup").unwrap();
 
-        EXECUTOR_INITIALIZED.store(true, core::sync::atomic::Ordering::Release);
+        EXECUTOR_INITIALIZED.store(true, Ordering::Release);
 
         Executor {
             event_handle: executor_event_handle,
@@ -163,15 +116,15 @@ impl Executor {
     pub fn block_on<F: IntoFuture>(&self, fut: F) -> F::Output {
         let mut fut = core::pin::pin!(fut.into_future());
 
-        let unused_index = {
-            let mut signals = SIGNALS.lock(WaitForever).unwrap();
-            let idx = signals
-                .into_iter()
-                .position(|p| SignalState::Unused == p)
-                .expect("No free task slots");
-            *signals.get_mut(idx).unwrap() = SignalState::Empty;
-            idx
-        };
+        let unused_index = SIGNALS_IN_USE
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                let free = (!used).trailing_zeros();
+                (free < u32::BITS).then(|| used | 1 << free)
+            })
+            .map(|used| (!used).trailing_zeros())
+            .expect("No free task slots");
+        // A waker of the previous user of the slot may have set the flag
+        self.event_handle.clear(1 << unused_index).unwrap();
 
         // Signal used to wake up the thread for polling as the future moves to completion. We need to use an `Arc`
         // because, although the lifetime of `fut` is limited to this function, the underlying IO abstraction might keep
@@ -191,9 +144,8 @@ impl Executor {
             }
         };
 
-        // Reset the signal
-        let mut signals = SIGNALS.lock(WaitForever).unwrap();
-        *signals.get_mut(unused_index).unwrap() = SignalState::Unused;
+        // Free the slot. Wakers still held elsewhere only cause a spurious poll of its next user.
+        SIGNALS_IN_USE.fetch_and(!(1 << unused_index), Ordering::Release);
 
         item
     }
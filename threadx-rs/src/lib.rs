@@ -1,18 +1,31 @@
 #![no_std]
+use core::cell::UnsafeCell;
 use core::ffi::c_void;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::task::Waker;
 
-use threadx_sys::_tx_initialize_kernel_enter;
+use core::time::Duration;
+
+use error::TxError;
+use num_traits::FromPrimitive;
+use threadx_sys::{_tx_initialize_kernel_enter, _tx_thread_interrupt_control, _tx_thread_sleep, TX_INT_DISABLE};
+use threadx_sys::{TX_NO_WAIT, TX_WAIT_FOREVER, ULONG};
+use time::TxTicks;
 
 pub mod allocator;
+pub mod clock;
+pub mod condvar;
 pub mod error;
 pub mod event_flags;
 pub mod mutex;
 pub mod pool;
 pub mod queue;
+pub mod rwlock;
 pub mod semaphore;
 pub mod thread;
 pub mod time;
 pub mod timer;
+#[cfg(feature = "alloc")]
 pub mod executor;
 
 pub use threadx_sys::__tx_PendSVHandler as tx_pendsv_handler;
@@ -137,8 +150,239 @@ macro_rules! tx_checked_call {
     }
 }
 
-#[repr(u32)]
+/// Run `f` with interrupts disabled. Keep `f` short and never block in it.
+pub(crate) fn critical_section<R>(f: impl FnOnce() -> R) -> R {
+    // Safety: The previous interrupt posture is restored before returning.
+    let posture = unsafe { _tx_thread_interrupt_control(TX_INT_DISABLE) };
+    let result = f();
+    unsafe { _tx_thread_interrupt_control(posture) };
+    result
+}
+
+/// Tracks the creation of a ThreadX object created on first use. The creation is claimed atomically
+/// and runs outside of any critical section, since ThreadX services must not be called with
+/// interrupts disabled.
+pub(crate) struct CreateOnce(AtomicU8);
+
+const NOT_CREATED: u8 = 0;
+const CREATING: u8 = 1;
+const CREATED: u8 = 2;
+
+impl CreateOnce {
+    pub(crate) const fn new() -> CreateOnce {
+        CreateOnce(AtomicU8::new(NOT_CREATED))
+    }
+
+    pub(crate) fn is_created(&self) -> bool {
+        self.0.load(Ordering::Acquire) == CREATED
+    }
+
+    /// Run `create` unless the object was created already. A thread racing with the creation waits
+    /// until it finished. If `create` fails the next call tries again.
+    pub(crate) fn call(&self, create: impl FnOnce() -> Result<(), TxError>) -> Result<(), TxError> {
+        loop {
+            match self.0.compare_exchange(NOT_CREATED, CREATING, Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => {
+                    let result = create();
+                    let state = if result.is_ok() { CREATED } else { NOT_CREATED };
+                    self.0.store(state, Ordering::Release);
+                    return result;
+                }
+                Err(CREATED) => return Ok(()),
+                // Sleep instead of spinning, the creating thread may have a lower priority
+                Err(_) => tx_checked_call!(_tx_thread_sleep(1))?,
+            }
+        }
+    }
+}
+
+/// Closure called by a ThreadX notification, e.g. when a semaphore is put. ThreadX runs it in
+/// the context of the caller of the notifying service, which may be an interrupt handler, so it
+/// must not block. Closures capturing data can be made `'static` with `Box::leak` or `StaticCell`.
+pub type NotifyFn<A> = &'static (dyn Fn(A) + Sync);
+
+/// Slot holding the `NotifyFn` of a ThreadX object next to its control block.
+pub(crate) struct NotifySlot<A: 'static>(UnsafeCell<Option<NotifyFn<A>>>);
+
+/// Safety: The slot is only accessed within critical sections.
+unsafe impl<A> Sync for NotifySlot<A> {}
+
+impl<A> NotifySlot<A> {
+    pub(crate) const fn new() -> NotifySlot<A> {
+        NotifySlot(UnsafeCell::new(None))
+    }
+
+    pub(crate) fn set(&self, notify: Option<NotifyFn<A>>) {
+        critical_section(|| unsafe { *self.0.get() = notify });
+    }
+
+    pub(crate) fn call(&self, arg: A) {
+        // The closure is `'static`, so it stays valid even if it is replaced while running.
+        if let Some(notify) = critical_section(|| unsafe { *self.0.get() }) {
+            notify(arg);
+        }
+    }
+}
+
+/// Wakers of up to `N` tasks waiting for a ThreadX object. They are woken from whatever context
+/// signals the object, so they must be safe to wake from there. Each waiting future holds a
+/// `WakerRegistration` which removes its waker again when the future completes or is dropped.
+pub(crate) struct WakerSet<const N: usize>(UnsafeCell<WakerEntries<N>>);
+
+struct WakerEntries<const N: usize> {
+    // Registration id and waker of each waiting task
+    wakers: [Option<(u32, Waker)>; N],
+    next_id: u32,
+}
+
+/// Safety: The entries are only accessed within critical sections.
+unsafe impl<const N: usize> Sync for WakerSet<N> {}
+
+impl<const N: usize> WakerSet<N> {
+    pub(crate) const fn new() -> WakerSet<N> {
+        WakerSet(UnsafeCell::new(WakerEntries {
+            wakers: [const { None }; N],
+            next_id: 0,
+        }))
+    }
+
+    fn with_entries<R>(&self, f: impl FnOnce(&mut WakerEntries<N>) -> R) -> R {
+        // Safety: The entries are only accessed with interrupts disabled.
+        critical_section(|| f(unsafe { &mut *self.0.get() }))
+    }
+
+    /// Wake all registered tasks. Their registrations stay valid, so they can register again.
+    pub(crate) fn wake_all(&self) {
+        let wakers = self.with_entries(|entries| {
+            core::array::from_fn::<_, N, _>(|i| entries.wakers[i].take().map(|(_, waker)| waker))
+        });
+        for waker in wakers.into_iter().flatten() {
+            waker.wake();
+        }
+    }
+}
+
+/// The entry of one waiting future in a `WakerSet`.
+pub(crate) struct WakerRegistration<'a, const N: usize> {
+    set: &'a WakerSet<N>,
+    // Index and id of the entry, the index may be reused by others once the entry was woken
+    entry: Option<(usize, u32)>,
+}
+
+impl<'a, const N: usize> WakerRegistration<'a, N> {
+    pub(crate) fn new(set: &'a WakerSet<N>) -> WakerRegistration<'a, N> {
+        WakerRegistration { set, entry: None }
+    }
+
+    /// Store `waker` until it is woken. Fails with `TxError::NotAvailable` if `N` other tasks
+    /// are already waiting.
+    pub(crate) fn register(&mut self, waker: &Waker) -> Result<(), TxError> {
+        let entry = self.entry;
+        // Wakers are cloned and dropped outside of the critical section
+        let mut new = Some(waker.clone());
+        let mut replaced = None;
+        let registered = self.set.with_entries(|entries| {
+            if let Some((index, id)) = entry {
+                match &mut entries.wakers[index] {
+                    Some((current, stored)) if *current == id => {
+                        if !stored.will_wake(waker) {
+                            replaced = new.take().map(|new| core::mem::replace(stored, new));
+                        }
+                        return Some((index, id));
+                    }
+                    slot @ None => {
+                        *slot = new.take().map(|new| (id, new));
+                        return Some((index, id));
+                    }
+                    // Taken over by another task after our waker was woken
+                    Some(_) => {}
+                }
+            }
+            let index = entries.wakers.iter().position(Option::is_none)?;
+            let id = entries.next_id;
+            entries.next_id = id.wrapping_add(1);
+            entries.wakers[index] = new.take().map(|new| (id, new));
+            Some((index, id))
+        });
+        drop((new, replaced));
+        self.entry = registered;
+        registered.map(|_| ()).ok_or(TxError::NotAvailable)
+    }
+}
+
+impl<const N: usize> Drop for WakerRegistration<'_, N> {
+    fn drop(&mut self) {
+        if let Some((index, id)) = self.entry {
+            let waker = self.set.with_entries(|entries| match &entries.wakers[index] {
+                Some((current, _)) if *current == id => entries.wakers[index].take(),
+                _ => None,
+            });
+            // Dropped outside of the critical section
+            drop(waker);
+        }
+    }
+}
+
+/// How long a blocking call waits for the resource to become available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum WaitOption {
-    WaitForever = threadx_sys::TX_WAIT_FOREVER,
-    NoWait = threadx_sys::TX_NO_WAIT,
+    WaitForever,
+    NoWait,
+    /// Wait at most the given number of ticks. If the wait expires the call
+    /// fails with `TxError::Timeout`.
+    Timeout(TxTicks),
+}
+
+impl WaitOption {
+    /// Wait at most `d`, rounded up to whole ticks.
+    pub fn timeout(d: Duration) -> WaitOption {
+        WaitOption::Timeout(TxTicks::from(d))
+    }
+
+    // ThreadX reports an expired wait with the same code as an unavailable
+    // resource. Only calls that did not wait keep that code.
+    pub(crate) fn timed_out(self, error: TxError) -> TxError {
+        match (self, error) {
+            (WaitOption::NoWait, error) => error,
+            (
+                _,
+                TxError::NotAvailable
+                | TxError::QueueEmpty
+                | TxError::QueueFull
+                | TxError::NoEvents
+                | TxError::NoInstance
+                | TxError::NoMemoryOrStartError,
+            ) => TxError::Timeout,
+            (_, error) => error,
+        }
+    }
+
+    /// What is left of this wait option after waiting since `start`. Used by
+    /// calls which block on several ThreadX objects one after the other.
+    pub(crate) fn remaining(self, start: time::Instant) -> WaitOption {
+        match self {
+            WaitOption::Timeout(ticks) => {
+                let elapsed = time::Instant::now().ticks_since(start).max(0) as u32;
+                WaitOption::Timeout(TxTicks::new(ticks.ticks().saturating_sub(elapsed)))
+            }
+            wait_option => wait_option,
+        }
+    }
+}
+
+impl From<Duration> for WaitOption {
+    fn from(d: Duration) -> WaitOption {
+        WaitOption::timeout(d)
+    }
+}
+
+impl From<WaitOption> for ULONG {
+    fn from(wait_option: WaitOption) -> ULONG {
+        match wait_option {
+            WaitOption::WaitForever => TX_WAIT_FOREVER,
+            WaitOption::NoWait => TX_NO_WAIT,
+            // The largest tick count means wait forever to ThreadX.
+            WaitOption::Timeout(ticks) => ticks.ticks().min(TX_WAIT_FOREVER - 1),
+        }
+    }
 }
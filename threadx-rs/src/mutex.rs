@@ -1,8 +1,11 @@
 use core::cell::UnsafeCell;
 use core::ffi::CStr;
+use core::fmt;
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
 use core::ops::Deref;
 use core::ops::DerefMut;
+use core::ptr::NonNull;
 
 /*
 UINT        _tx_mutex_create(TX_MUTEX *mutex_ptr, CHAR *name_ptr, UINT inherit);
@@ -19,52 +22,213 @@ UINT        _tx_mutex_prioritize(TX_MUTEX *mutex_ptr);
 UINT        _tx_mutex_put(TX_MUTEX *mutex_ptr);
 
 */
-use crate::tx_checked_call;
+use crate::CreateOnce;
+use crate::{tx_checked_call, tx_checked_call_no_log};
 
 use super::error::TxError;
 use super::WaitOption;
+use core::sync::atomic::{AtomicBool, Ordering};
 use defmt::error;
-use defmt::println;
 use num_traits::FromPrimitive;
 use thiserror_no_std::Error;
 use threadx_sys::_tx_mutex_create;
 use threadx_sys::_tx_mutex_delete;
 use threadx_sys::_tx_mutex_get;
 use threadx_sys::_tx_mutex_put;
+use threadx_sys::_tx_thread_identify;
 use threadx_sys::TX_MUTEX;
-use threadx_sys::TX_MUTEX_STRUCT;
-
-/// Safety: TODO: Is it safe to copy the TX_MUTEX structure? Currently nothing forbids rust to just move it ie. memcopy it.
-/// Maybe initialization of the mutex should return a handle containing the pointer. This should be safe and the can live as long as the mutex.
-/// But that maybe not enough since the original mutex struct can still be moved around...
-/// ThreadX documentation:
-/// Mutex Control Block TX_MUTEX
-/// The characteristics of each mutex are found in its control block. It contains information such as the current mutex ownership count along with the pointer
-/// of the thread that owns the mutex. This structure is defined in the tx_api.h file. Mutex control blocks can be located anywhere in memory, but
-/// it is most common to make the control block a global structure by defining it outside the scope of any function.
-///
-/// Problem with the current implementations is that it is not possible to create a static mutex and work with it. Both structures need a &mut
-/// to initialize which you do not have with a static. One could use StaticCell and share the reference but that does not seem so nice.
-pub struct Mutex<T> {
-    inner: UnsafeCell<T>,
+use threadx_sys::{_tx_mutex_info_get, _tx_mutex_performance_info_get};
+use threadx_sys::{_tx_mutex_performance_system_info_get, CHAR, TX_THREAD, ULONG};
+
+use crate::thread::ThreadId;
+
+/// A ThreadX mutex without data, created on first use. Shared by the lock types of this crate.
+pub(crate) struct RawMutex {
     // TX_MUTEX control block
     mutex: UnsafeCell<MaybeUninit<TX_MUTEX>>,
-    initialized: bool,
+    created: CreateOnce,
+    name: Option<&'static CStr>,
+    inherit: bool,
 }
-/// Safety: Initialization is done via a &mut reference hence thread safe
-unsafe impl<T: Send> Send for Mutex<T> {}
-unsafe impl<T: Send> Sync for Mutex<T> {}
-pub struct StaticMutex<T> {
+
+/// Safety: The control block is created exactly once, claimed through `CreateOnce`, afterwards it is
+/// only accessed via ThreadX calls.
+unsafe impl Send for RawMutex {}
+unsafe impl Sync for RawMutex {}
+
+impl RawMutex {
+    pub(crate) const fn new(name: Option<&'static CStr>, inherit: bool) -> RawMutex {
+        RawMutex {
+            mutex: UnsafeCell::new(MaybeUninit::<TX_MUTEX>::uninit()),
+            created: CreateOnce::new(),
+            name,
+            inherit,
+        }
+    }
+
+    fn raw(&self) -> *mut TX_MUTEX {
+        self.mutex.get().cast()
+    }
+
+    /// Returns the control block, creating the ThreadX mutex on first call.
+    pub(crate) fn mutex_ptr(&'static self) -> Result<*mut TX_MUTEX, TxError> {
+        self.created.call(|| {
+            let name = self
+                .name
+                .map_or(core::ptr::null_mut(), |n| n.as_ptr() as *mut CHAR);
+            tx_checked_call!(_tx_mutex_create(self.raw(), name, self.inherit as u32))
+        })?;
+        Ok(self.raw())
+    }
+
+    /// True if the calling thread owns the mutex.
+    pub(crate) fn held_by_current_thread(&self) -> bool {
+        if !self.created.is_created() {
+            return false;
+        }
+        // Safety: Only the current thread can make itself the owner, so the value read is stable
+        // with respect to the comparison.
+        let owner = unsafe { core::ptr::addr_of!((*self.raw()).tx_mutex_owner).read_volatile() };
+        !owner.is_null() && owner == unsafe { _tx_thread_identify() }
+    }
+
+    pub(crate) fn acquire(&'static self, wait_option: WaitOption) -> Result<(), TxError> {
+        let mutex_ptr = self.mutex_ptr()?;
+        let result = match wait_option {
+            WaitOption::NoWait => {
+                tx_checked_call_no_log!(_tx_mutex_get(mutex_ptr, wait_option.into()))
+            }
+            _ => tx_checked_call!(_tx_mutex_get(mutex_ptr, wait_option.into())),
+        };
+        result.map_err(|e| wait_option.timed_out(e))
+    }
+
+    pub(crate) fn get<G>(&'static self, wait_option: WaitOption) -> Result<(), MutexError<G>> {
+        self.acquire(wait_option).map_err(|e| match e {
+            TxError::NotAvailable if wait_option == WaitOption::NoWait => MutexError::WouldBlock,
+            e => MutexError::MutexError(e),
+        })
+    }
+
+    /// Releases the mutex once. Must only be called by the owning thread.
+    pub(crate) fn put(&self) {
+        if tx_checked_call!(_tx_mutex_put(self.raw())).is_err() {
+            error!("MutexGuard::drop failed to put mutex");
+        }
+    }
+
+    fn info(&'static self) -> Result<MutexInfo<'static>, TxError> {
+        let mut name: *mut CHAR = core::ptr::null_mut();
+        let mut count: ULONG = 0;
+        let mut owner: *mut TX_THREAD = core::ptr::null_mut();
+        let mut suspended_count: ULONG = 0;
+        tx_checked_call!(_tx_mutex_info_get(
+            self.mutex_ptr()?,
+            &mut name,
+            &mut count,
+            &mut owner,
+            core::ptr::null_mut(),
+            &mut suspended_count,
+            core::ptr::null_mut()
+        ))?;
+        Ok(MutexInfo {
+            // Safety: ThreadX keeps the name passed to `with_options`.
+            name: (!name.is_null()).then(|| unsafe { CStr::from_ptr(name.cast()) }),
+            owner: ThreadId::from_ptr(owner),
+            ownership_count: count,
+            suspended_count,
+        })
+    }
+
+    fn performance(&'static self) -> Result<MutexPerformance, TxError> {
+        let mut perf = [0 as ULONG; 6];
+        let [puts, gets, suspensions, timeouts, inversions, inheritances] = &mut perf;
+        tx_checked_call!(_tx_mutex_performance_info_get(
+            self.mutex_ptr()?,
+            puts,
+            gets,
+            suspensions,
+            timeouts,
+            inversions,
+            inheritances
+        ))?;
+        Ok(MutexPerformance::from(perf))
+    }
+}
+
+impl Drop for RawMutex {
+    fn drop(&mut self) {
+        if !self.created.is_created() {
+            // Nothing to drop, we rely on rusts recursive drop
+            return;
+        }
+        let _ = tx_checked_call!(_tx_mutex_delete(self.raw()));
+    }
+}
+
+/// Mutex protecting a value of type `T`.
+///
+/// The mutex can be declared as a `static` since it is const constructible:
+///
+/// ```ignore
+/// static COUNTER: Mutex<u32> = Mutex::new(0);
+/// *COUNTER.lock(WaitOption::WaitForever).unwrap() += 1;
+/// ```
+///
+/// The ThreadX control block is created on the first use of the mutex. ThreadX links all created mutexes
+/// into a list, so the control block must never move once created. This is guaranteed by requiring
+/// `&'static self` for all operations which create or use the control block.
+///
+/// ThreadX mutexes are recursive, but a second `MutexGuard` would alias the `&mut T` of the first one.
+/// Locking a mutex already held by the calling thread therefore fails with `MutexError::AlreadyHeld`.
+/// Use `ReentrantMutex` for recursive locking.
+///
+/// ThreadX releases the mutexes of a terminated thread. If that thread still held a guard, the data
+/// may be left in an inconsistent state and the mutex becomes poisoned: all further `lock` calls return
+/// `MutexError::PoisonError`, which still grants access to the data for recovery.
+pub struct Mutex<T> {
     inner: UnsafeCell<T>,
-    mutex: UnsafeCell<*mut TX_MUTEX>,
+    raw: RawMutex,
+    poison: Poison,
 }
 
-/// Safety: Initialization is done via a &mut reference hence thread safe
-unsafe impl<T: Send> Send for StaticMutex<T> {}
-unsafe impl<T: Send> Sync for StaticMutex<T> {}
+/// Poison state of a `Mutex`, only modified while holding the ThreadX mutex.
+struct Poison {
+    poisoned: AtomicBool,
+    // Set while a guard exists. Still set when acquiring the mutex means the previous owner never
+    // dropped its guard.
+    guarded: AtomicBool,
+}
+
+impl Poison {
+    const fn new() -> Poison {
+        Poison {
+            poisoned: AtomicBool::new(false),
+            guarded: AtomicBool::new(false),
+        }
+    }
+
+    /// Called after acquiring the mutex. Returns true if the mutex is poisoned.
+    fn acquire(&self) -> bool {
+        if self.guarded.swap(true, Ordering::Relaxed) {
+            self.poisoned.store(true, Ordering::Relaxed);
+        }
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Called before releasing the mutex.
+    fn release(&self) {
+        self.guarded.store(false, Ordering::Relaxed);
+    }
+}
+/// Safety: The inner value is only accessed while holding the ThreadX mutex.
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
 
+/// Guards must be dropped by the thread which locked the mutex, hence they are not `Send`.
 pub struct MutexGuard<'a, T> {
     mutex: &'a Mutex<T>,
+    _not_send: PhantomData<*const ()>,
 }
 
 impl<T> Deref for MutexGuard<'_, T> {
@@ -83,133 +247,375 @@ impl<T> DerefMut for MutexGuard<'_, T> {
 
 impl<T> Drop for MutexGuard<'_, T> {
     fn drop(&mut self) {
-        let mutex_ptr = self.mutex.mutex.get();
-        if let Some(mutex_ptr) = unsafe { mutex_ptr.as_mut() } {
-            if tx_checked_call!(_tx_mutex_put(mutex_ptr.as_mut_ptr())).is_err() {
-                error!("MutexGuard::drop failed to put mutex");
+        self.mutex.poison.release();
+        self.mutex.raw.put();
+    }
+}
+
+impl<'a, T> MutexGuard<'a, T> {
+    /// Releases the mutex and returns it so it can be locked again.
+    pub(crate) fn unlock(self) -> &'a Mutex<T> {
+        self.mutex
+    }
+
+    /// Makes a guard for a component of the locked data.
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedMutexGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let mutex = orig.mutex;
+        // Safety: The mutex is held until the mapped guard is dropped.
+        let data = NonNull::from(f(unsafe { &mut *mutex.inner.get() }));
+        core::mem::forget(orig);
+        MappedMutexGuard::new(&mutex.raw, &mutex.poison, data)
+    }
+
+    /// Like `map` but `f` may fail, in which case the original guard is returned.
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedMutexGuard<'a, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        let mutex = orig.mutex;
+        // Safety: The mutex is held until the mapped guard is dropped.
+        match f(unsafe { &mut *mutex.inner.get() }) {
+            Some(data) => {
+                let data = NonNull::from(data);
+                core::mem::forget(orig);
+                Ok(MappedMutexGuard::new(&mutex.raw, &mutex.poison, data))
+            }
+            None => Err(orig),
+        }
+    }
+}
+
+/// Guard returned by `MutexGuard::map`, giving access to a part of the locked data.
+pub struct MappedMutexGuard<'a, U: ?Sized> {
+    raw: &'a RawMutex,
+    poison: &'a Poison,
+    data: NonNull<U>,
+    _marker: PhantomData<(&'a mut U, *const ())>,
+}
+
+impl<'a, U: ?Sized> MappedMutexGuard<'a, U> {
+    fn new(raw: &'a RawMutex, poison: &'a Poison, data: NonNull<U>) -> Self {
+        MappedMutexGuard {
+            raw,
+            poison,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes a guard for a component of the already mapped data.
+    pub fn map<V: ?Sized, F>(mut orig: Self, f: F) -> MappedMutexGuard<'a, V>
+    where
+        F: FnOnce(&mut U) -> &mut V,
+    {
+        let data = NonNull::from(f(unsafe { orig.data.as_mut() }));
+        let (raw, poison) = (orig.raw, orig.poison);
+        core::mem::forget(orig);
+        MappedMutexGuard::new(raw, poison, data)
+    }
+
+    /// Like `map` but `f` may fail, in which case the original guard is returned.
+    pub fn try_map<V: ?Sized, F>(mut orig: Self, f: F) -> Result<MappedMutexGuard<'a, V>, Self>
+    where
+        F: FnOnce(&mut U) -> Option<&mut V>,
+    {
+        match f(unsafe { orig.data.as_mut() }) {
+            Some(data) => {
+                let data = NonNull::from(data);
+                let (raw, poison) = (orig.raw, orig.poison);
+                core::mem::forget(orig);
+                Ok(MappedMutexGuard::new(raw, poison, data))
             }
-        } else {
-            panic!("Mutex ptr is null");
+            None => Err(orig),
         }
     }
 }
-pub struct MutexGuardStatic<'a, T> {
-    mutex: &'a StaticMutex<T>,
+
+impl<U: ?Sized> Deref for MappedMutexGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<U: ?Sized> DerefMut for MappedMutexGuard<'_, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl<U: ?Sized> Drop for MappedMutexGuard<'_, U> {
+    fn drop(&mut self) {
+        self.poison.release();
+        self.raw.put();
+    }
+}
+
+/// Recursive mutex protecting a value of type `T`.
+///
+/// The owning thread may lock the mutex again while holding it. Since several guards can exist at
+/// the same time, they only hand out `&T`. Use `Cell` or `RefCell` for mutation.
+pub struct ReentrantMutex<T> {
+    inner: T,
+    raw: RawMutex,
 }
+/// Safety: The inner value is only accessed by the thread holding the ThreadX mutex.
+unsafe impl<T: Send> Send for ReentrantMutex<T> {}
+unsafe impl<T: Send> Sync for ReentrantMutex<T> {}
 
-impl<T> Deref for MutexGuardStatic<'_, T> {
+pub struct ReentrantMutexGuard<'a, T> {
+    mutex: &'a ReentrantMutex<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T> Deref for ReentrantMutexGuard<'_, T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { &*self.mutex.inner.get() }
+        &self.mutex.inner
     }
 }
 
-impl<T> DerefMut for MutexGuardStatic<'_, T> {
-    fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.mutex.inner.get() }
+impl<T> Drop for ReentrantMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.raw.put();
     }
 }
 
-impl<T> Drop for MutexGuardStatic<'_, T> {
-    fn drop(&mut self) {
-        let mutex_ptr = unsafe { self.mutex.mutex.get().read() };
-        if tx_checked_call!(_tx_mutex_put(mutex_ptr)).is_err() {
-            error!("MutexGuard::drop failed to put mutex");
-        };
+/// Snapshot of a mutex returned by `Mutex::info`.
+#[derive(Debug)]
+pub struct MutexInfo<'a> {
+    pub name: Option<&'a CStr>,
+    /// Thread currently holding the mutex.
+    pub owner: Option<ThreadId>,
+    /// Number of times the owner locked the mutex.
+    pub ownership_count: u32,
+    /// Number of threads waiting for the mutex.
+    pub suspended_count: u32,
+}
+
+/// Counters returned by `Mutex::performance` and `system_performance`.
+/// ThreadX must be built with `TX_MUTEX_ENABLE_PERFORMANCE_INFO`, otherwise
+/// `TxError::FeatureNotEnabled` is returned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MutexPerformance {
+    pub puts: u32,
+    pub gets: u32,
+    pub suspensions: u32,
+    pub timeouts: u32,
+    /// Number of times a lower priority thread held the mutex a higher
+    /// priority thread waited for.
+    pub inversions: u32,
+    /// Number of times the priority of the owner was raised.
+    pub inheritances: u32,
+}
+
+/// Performance counters summed up over all mutexes.
+pub fn system_performance() -> Result<MutexPerformance, TxError> {
+    let mut perf = [0 as ULONG; 6];
+    let [puts, gets, suspensions, timeouts, inversions, inheritances] = &mut perf;
+    tx_checked_call!(_tx_mutex_performance_system_info_get(
+        puts,
+        gets,
+        suspensions,
+        timeouts,
+        inversions,
+        inheritances
+    ))?;
+    Ok(MutexPerformance::from(perf))
+}
+
+impl From<[ULONG; 6]> for MutexPerformance {
+    fn from(perf: [ULONG; 6]) -> MutexPerformance {
+        let [puts, gets, suspensions, timeouts, inversions, inheritances] = perf;
+        MutexPerformance {
+            puts,
+            gets,
+            suspensions,
+            timeouts,
+            inversions,
+            inheritances,
+        }
     }
 }
 
-#[derive(Error, Debug)]
-pub enum MutexError {
+pub type LockResult<G> = Result<G, MutexError<G>>;
+
+/// Error returned by the lock functions. `G` is the guard handed out with a `PoisonError`.
+#[derive(Error)]
+pub enum MutexError<G = ()> {
     MutexError(TxError),
-    PoisonError,
+    PoisonError(PoisonError<G>),
+    /// `try_lock` found the mutex held by another thread.
+    WouldBlock,
+    /// The calling thread already holds the (non reentrant) mutex.
+    AlreadyHeld,
 }
 
-impl<T> StaticMutex<T> {
-    pub const fn new(inner: T, mutex: *mut TX_MUTEX) -> StaticMutex<T> {
-        StaticMutex {
-            inner: UnsafeCell::new(inner),
-            mutex: UnsafeCell::new(mutex),
+impl<G> fmt::Debug for MutexError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MutexError::MutexError(e) => f.debug_tuple("MutexError").field(e).finish(),
+            MutexError::PoisonError(e) => f.debug_tuple("PoisonError").field(e).finish(),
+            MutexError::WouldBlock => f.write_str("WouldBlock"),
+            MutexError::AlreadyHeld => f.write_str("AlreadyHeld"),
         }
     }
-    pub fn initialize(&self, name: &CStr, inherit: bool) -> Result<(), TxError> {
-        let mutex_ptr = unsafe { self.mutex.get().read() };
-        let res = tx_checked_call!(_tx_mutex_create(
-            mutex_ptr,
-            name.as_ptr() as *mut i8,
-            inherit as u32
-        ));
-        res
-    }
-    pub fn lock(&self, wait_option: WaitOption) -> Result<MutexGuardStatic<'_, T>, MutexError> {
-        let mutex_ptr = unsafe { self.mutex.get().read() };
-        let result = tx_checked_call!(_tx_mutex_get(mutex_ptr, wait_option as u32));
-        match result {
-            Ok(_) => Ok(MutexGuardStatic { mutex: self }),
-            Err(e) => Err(MutexError::MutexError(e)),
+}
+
+/// The mutex was poisoned. Holds the guard so the data can still be accessed.
+pub struct PoisonError<G> {
+    guard: G,
+}
+
+impl<G> MutexError<G> {
+    /// Replaces the guard carried by a `PoisonError`.
+    pub(crate) fn map_guard<H>(self, f: impl FnOnce(G) -> H) -> MutexError<H> {
+        match self {
+            MutexError::MutexError(e) => MutexError::MutexError(e),
+            MutexError::PoisonError(e) => {
+                MutexError::PoisonError(PoisonError { guard: f(e.guard) })
+            }
+            MutexError::WouldBlock => MutexError::WouldBlock,
+            MutexError::AlreadyHeld => MutexError::AlreadyHeld,
         }
     }
 }
-impl<T> Drop for StaticMutex<T> {
-    fn drop(&mut self) {
-        let mutex_ptr = unsafe { self.mutex.get().read() };
 
-        let _ = tx_checked_call!(_tx_mutex_delete(mutex_ptr));
+impl<G> PoisonError<G> {
+    pub fn into_inner(self) -> G {
+        self.guard
+    }
+
+    pub fn get_ref(&self) -> &G {
+        &self.guard
+    }
+
+    pub fn get_mut(&mut self) -> &mut G {
+        &mut self.guard
     }
 }
+
+impl<G> fmt::Debug for PoisonError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoisonError").finish_non_exhaustive()
+    }
+}
+
 // Mutex implementation
 impl<T> Mutex<T> {
+    /// Creates an unnamed mutex without priority inheritance.
     pub const fn new(inner: T) -> Mutex<T> {
+        Mutex::with_options(inner, None, false)
+    }
+
+    /// Creates a mutex with a name shown by debuggers and optional priority inheritance.
+    pub const fn with_options(inner: T, name: Option<&'static CStr>, inherit: bool) -> Mutex<T> {
         Mutex {
             inner: UnsafeCell::new(inner),
-            mutex: UnsafeCell::new(MaybeUninit::<TX_MUTEX>::uninit()),
-            initialized: false,
+            raw: RawMutex::new(name, inherit),
+            poison: Poison::new(),
         }
     }
-}
-impl<T> Mutex<T> {
-    pub fn initialize(&mut self, name: &CStr, inherit: bool) -> Result<(), TxError> {
-        if self.initialized {
-            // If mutex was already initialized we just return Ok
-            return Ok(());
-        }
-        let mutex_ptr = self.mutex.get_mut().as_mut_ptr();
-        let res = tx_checked_call!(_tx_mutex_create(
-            mutex_ptr,
-            name.as_ptr() as *mut i8,
-            inherit as u32
-        ));
-        if res.is_ok() {
-            self.initialized = true;
-        }
-        res
-    }
-    pub fn lock(&self, wait_option: WaitOption) -> Result<MutexGuard<'_, T>, MutexError> {
-        if !self.initialized {
-            return Err(MutexError::PoisonError);
-        }
-        let mutex_ptr = self.mutex.get();
-
-        if let Some(mutex_ptr) = unsafe { mutex_ptr.as_mut() } {
-            let mutex_ptr = mutex_ptr.as_mut_ptr();
-            let result = tx_checked_call!(_tx_mutex_get(mutex_ptr, wait_option as u32));
-            match result {
-                Ok(_) => Ok(MutexGuard { mutex: self }),
-                Err(e) => Err(MutexError::MutexError(e)),
-            }
-        } else {
-            return Err(MutexError::PoisonError);
+
+    /// Creates the ThreadX mutex unless this already happened. Calling this is optional since
+    /// the mutex is created on first use, but it allows to handle creation errors early.
+    pub fn initialize(&'static self) -> Result<(), TxError> {
+        self.raw.mutex_ptr().map(|_| ())
+    }
+
+    pub fn lock(&'static self, wait_option: WaitOption) -> LockResult<MutexGuard<'static, T>> {
+        if self.raw.held_by_current_thread() {
+            return Err(MutexError::AlreadyHeld);
         }
+        self.raw.get(wait_option)?;
+        let guard = MutexGuard {
+            mutex: self,
+            _not_send: PhantomData,
+        };
+        if self.poison.acquire() {
+            return Err(MutexError::PoisonError(PoisonError { guard }));
+        }
+        Ok(guard)
+    }
+
+    /// Locks the mutex without waiting. Returns `MutexError::WouldBlock` if it is held by another thread.
+    pub fn try_lock(&'static self) -> LockResult<MutexGuard<'static, T>> {
+        self.lock(WaitOption::NoWait)
+    }
+
+    /// True if a thread was terminated while holding the mutex. A terminated owner which is only
+    /// detected by the next `lock` call is not reported here.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Marks the data as consistent again after recovering from a poisoned lock.
+    pub fn clear_poison(&self) {
+        self.poison.poisoned.store(false, Ordering::Relaxed);
+    }
+
+    /// Query owner, ownership count and number of waiting threads.
+    pub fn info(&'static self) -> Result<MutexInfo<'static>, TxError> {
+        self.raw.info()
+    }
+
+    /// Query the performance counters of the mutex.
+    pub fn performance(&'static self) -> Result<MutexPerformance, TxError> {
+        self.raw.performance()
     }
 }
-impl<T> Drop for Mutex<T> {
-    fn drop(&mut self) {
-        if !self.initialized {
-            // Nothing to drop, we rely on rusts recursive drop
-            return;
+
+impl<T> ReentrantMutex<T> {
+    /// Creates an unnamed reentrant mutex without priority inheritance.
+    pub const fn new(inner: T) -> ReentrantMutex<T> {
+        ReentrantMutex::with_options(inner, None, false)
+    }
+
+    /// Creates a reentrant mutex with a name shown by debuggers and optional priority inheritance.
+    pub const fn with_options(
+        inner: T,
+        name: Option<&'static CStr>,
+        inherit: bool,
+    ) -> ReentrantMutex<T> {
+        ReentrantMutex {
+            inner,
+            raw: RawMutex::new(name, inherit),
         }
-        let mutex_ptr = self.mutex.get_mut().as_mut_ptr();
-        let _ = tx_checked_call!(_tx_mutex_delete(mutex_ptr));
+    }
+
+    /// See `Mutex::initialize`.
+    pub fn initialize(&'static self) -> Result<(), TxError> {
+        self.raw.mutex_ptr().map(|_| ())
+    }
+
+    pub fn lock(
+        &'static self,
+        wait_option: WaitOption,
+    ) -> Result<ReentrantMutexGuard<'static, T>, MutexError> {
+        self.raw.get(wait_option)?;
+        Ok(ReentrantMutexGuard {
+            mutex: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Locks the mutex without waiting. Returns `MutexError::WouldBlock` if it is held by another thread.
+    pub fn try_lock(&'static self) -> Result<ReentrantMutexGuard<'static, T>, MutexError> {
+        self.lock(WaitOption::NoWait)
+    }
+
+    /// Query owner, ownership count and number of waiting threads.
+    pub fn info(&'static self) -> Result<MutexInfo<'static>, TxError> {
+        self.raw.info()
+    }
+
+    /// Query the performance counters of the mutex.
+    pub fn performance(&'static self) -> Result<MutexPerformance, TxError> {
+        self.raw.performance()
     }
 }
@@ -2,17 +2,18 @@ use core::{
     ffi::{c_void, CStr},
     marker::PhantomData,
     mem::MaybeUninit,
-
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
 };
 
 use threadx_sys::{
     _tx_block_allocate, _tx_block_pool_create, _tx_block_pool_delete, 
     _tx_block_pool_prioritize, _tx_block_release, _tx_byte_allocate, _tx_byte_pool_create,
-    _tx_byte_pool_delete, _tx_byte_release, TX_BLOCK_POOL, TX_BYTE_POOL, TX_NO_WAIT,
-    TX_WAIT_FOREVER, ULONG,
+    _tx_byte_pool_delete, _tx_byte_release, TX_BLOCK_POOL, TX_BYTE_POOL, ULONG,
 };
 
 use crate::tx_checked_call;
+use crate::WaitOption;
 
 use super::error::TxError;
 use defmt::error;
@@ -87,7 +88,7 @@ impl<'a> BytePoolHandle<'a> {
     pub fn allocate(
         &self,
         size: usize,
-        wait: bool,
+        wait: WaitOption,
     ) -> Result<MemoryBlock<'a>, TxError> {
         let mut ptr: *mut c_void = core::ptr::null_mut() as *mut c_void;
     
@@ -95,8 +96,9 @@ impl<'a> BytePoolHandle<'a> {
             self.pool_ptr,
             &mut ptr,
             size as ULONG,
-            if wait { TX_WAIT_FOREVER } else { TX_NO_WAIT }
+            wait.into()
         ))
+        .map_err(|e| wait.timed_out(e))
         .map(|_| MemoryBlock(unsafe { core::slice::from_raw_parts_mut(ptr as *mut u8, size) }))
     }    
 
@@ -139,13 +141,14 @@ pub struct BlockPoolHandle<'a> (*mut TX_BLOCK_POOL, PhantomData<&'a [u8]>,
 );
 
 impl<'memory> BlockPoolHandle<'memory> {
-    pub fn allocate(&mut self, wait: bool) -> Result<&'memory mut [u8], TxError> {
+    pub fn allocate(&mut self, wait: WaitOption) -> Result<&'memory mut [u8], TxError> {
         let mut ptr: *mut c_void = core::ptr::null_mut() as *mut c_void;
         tx_checked_call!(_tx_block_allocate(
             self.0,
             &mut ptr,
-            if wait { TX_WAIT_FOREVER } else { TX_NO_WAIT }
+            wait.into()
         ))
+        .map_err(|e| wait.timed_out(e))
         .map(|_| unsafe {
             core::slice::from_raw_parts_mut(
                 ptr as *mut u8,
@@ -158,6 +161,20 @@ impl<'memory> BlockPoolHandle<'memory> {
         tx_checked_call!(_tx_block_release(mem.as_mut_ptr() as *mut c_void))
     }
 
+    /// Move `value` into a block of the pool. The value is handed back if allocation fails.
+    pub fn allocate_box<T>(&mut self, value: T, wait: WaitOption) -> Result<PoolBox<T>, (T, TxError)> {
+        PoolBox::new_in(self.0, value, wait)
+    }
+
+    /// Size of the blocks in bytes.
+    pub fn block_size(&self) -> usize {
+        unsafe { (*self.0).tx_block_pool_block_size as usize }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut TX_BLOCK_POOL {
+        self.0
+    }
+
     /*
         #define tx_block_allocate                           _tx_block_allocate
     #define tx_block_pool_create                        _tx_block_pool_create
@@ -178,3 +195,74 @@ impl<'memory> BlockPoolHandle<'memory> {
         tx_checked_call!(_tx_block_pool_delete(self.0))
     }
 }
+
+/// A value stored in a block of a `BlockPool`. On drop the value is dropped and the block released.
+///
+/// ThreadX aligns blocks to ULONG only, so `T` must not require a larger alignment. This is checked
+/// at compile time.
+pub struct PoolBox<T>(NonNull<T>);
+
+/// Safety: The box owns the value, blocks can be released from any thread.
+unsafe impl<T: Send> Send for PoolBox<T> {}
+unsafe impl<T: Sync> Sync for PoolBox<T> {}
+
+impl<T> PoolBox<T> {
+    pub(crate) const ALIGN_OK: () = assert!(
+        core::mem::align_of::<T>() <= core::mem::size_of::<ULONG>(),
+        "block pool blocks are only ULONG aligned"
+    );
+
+    pub(crate) fn new_in(pool_ptr: *mut TX_BLOCK_POOL, value: T, wait: WaitOption) -> Result<PoolBox<T>, (T, TxError)> {
+        let _ = Self::ALIGN_OK;
+        // Safety: The pool was created by `BlockPool::initialize`.
+        if unsafe { (*pool_ptr).tx_block_pool_block_size as usize } < core::mem::size_of::<T>() {
+            return Err((value, TxError::SizeError));
+        }
+        let mut ptr: *mut c_void = core::ptr::null_mut();
+        if let Err(e) = tx_checked_call!(_tx_block_allocate(pool_ptr, &mut ptr, wait.into())) {
+            return Err((value, wait.timed_out(e)));
+        }
+        let ptr = ptr.cast::<T>();
+        // Safety: The block is large enough, ULONG aligned which suffices for `T` and exclusively ours.
+        unsafe { ptr.write(value) };
+        Ok(PoolBox(unsafe { NonNull::new_unchecked(ptr) }))
+    }
+
+    /// Move the value out and release the block.
+    pub fn into_inner(this: Self) -> T {
+        let ptr = PoolBox::into_raw(this);
+        // Safety: The value is read once, then the block is released.
+        let value = unsafe { ptr.read() };
+        let _ = tx_checked_call!(_tx_block_release(ptr.cast()));
+        value
+    }
+
+    pub(crate) fn into_raw(this: Self) -> *mut T {
+        let ptr = this.0.as_ptr();
+        core::mem::forget(this);
+        ptr
+    }
+}
+
+impl<T> Deref for PoolBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl<T> DerefMut for PoolBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.0.as_mut() }
+    }
+}
+
+impl<T> Drop for PoolBox<T> {
+    fn drop(&mut self) {
+        unsafe { core::ptr::drop_in_place(self.0.as_ptr()) };
+        if tx_checked_call!(_tx_block_release(self.0.as_ptr().cast())).is_err() {
+            error!("PoolBox::drop failed to release block");
+        }
+    }
+}
@@ -18,31 +18,112 @@ UINT        _tx_queue_front_send(TX_QUEUE *queue_ptr, VOID *source_ptr, ULONG wa
 */
 
 use super::{error::TxError, WaitOption};
-use crate::{tx_checked_call, tx_checked_call_no_log};
-use core::mem::size_of;
-use core::{ffi::CStr, mem::MaybeUninit};
+use crate::{tx_checked_call, tx_checked_call_no_log, NotifyFn, NotifySlot, WakerRegistration, WakerSet};
+use core::future::poll_fn;
+use core::mem::{size_of, ManuallyDrop};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+use core::{cell::UnsafeCell, ffi::CStr, mem::MaybeUninit};
+use crate::pool::{BlockPoolHandle, PoolBox};
+use crate::time::Instant;
 use defmt::{error, println};
 use num_traits::FromPrimitive;
-use threadx_sys::{_tx_queue_create, _tx_queue_receive, _tx_queue_send, TX_QUEUE, ULONG};
+use threadx_sys::{_tx_queue_create, _tx_queue_receive, _tx_queue_send, _tx_queue_send_notify, TX_BLOCK_POOL, TX_QUEUE, ULONG};
+use threadx_sys::{_tx_queue_delete, _tx_queue_flush, _tx_queue_front_send, _tx_queue_info_get, _tx_queue_prioritize};
+use threadx_sys::{_tx_queue_performance_info_get, _tx_queue_performance_system_info_get, CHAR, TX_THREAD};
 
-/// Wrapper around the ThreadX queue. ThreadX will copy the message so the best approximation is to restrict the type to be Copy. 
-/// Since messages might be received by a different thread any reference must be valid for 'static. Note that the message struct will be dropped 
-/// at the end of this function. 
-pub struct Queue<T: Copy + 'static>(MaybeUninit<TX_QUEUE>, core::marker::PhantomData<T>);
+/// Wrapper around the ThreadX queue. ThreadX copies the message bytes, which moves the message to the receiver: the sender forgets
+/// the value after a successful send and gets it back otherwise. Since messages might be received by a different thread they must
+/// be `Send` and any reference must be valid for 'static. Messages still enqueued are dropped by `flush` and `delete`.
+/// The control block is the first field so the Rust side data can be found from the pointer ThreadX passes to callbacks.
+///
+/// With `N` > 0 the queue owns storage for `N` messages, see `initialize_owned`. Otherwise the memory is passed to `initialize`.
+#[repr(C)]
+pub struct Queue<T: Send + 'static, const N: usize = 0>(
+    MaybeUninit<TX_QUEUE>,
+    Shared<T>,
+    UnsafeCell<[MaybeUninit<Message<T>>; N]>,
+);
 
-impl<T: core::marker::Copy + 'static> Queue<T> {
+/// Rust side data of a queue, shared by its handles.
+struct Shared<T: 'static> {
+    send_notify: NotifySlot<QueueReceiverRef<T>>,
+    // Tasks waiting in `receive_async`, woken by the send notification
+    receiver_wakers: WakerSet<MAX_ASYNC_WAITERS>,
+    // Tasks waiting in `send_async`, woken when a message is received
+    sender_wakers: WakerSet<MAX_ASYNC_WAITERS>,
+    // Set by `QueueReceiver::delete`, senders may still exist
+    deleted: AtomicBool,
+}
+
+/// Number of tasks that can wait in `send_async` and in `receive_async` of the same queue at once.
+pub const MAX_ASYNC_WAITERS: usize = 4;
+
+/// Largest message ThreadX supports in ULONG words.
+const MAX_MESSAGE_WORDS: usize = 16;
+
+/// Number of ULONG words ThreadX copies per message of type `T`, at least one even for smaller types.
+const fn message_words<T>() -> usize {
+    size_of::<Message<T>>() / size_of::<ULONG>()
+}
+
+/// A message as ThreadX copies it. Its size is `message_words::<T>()` ULONG words and it is at least
+/// ULONG aligned, so an array of messages is valid queue storage. Smaller messages are padded to one word.
+#[repr(C)]
+union Message<T> {
+    value: ManuallyDrop<T>,
+    _word: ULONG,
+}
+
+impl<T: Send + 'static, const N: usize> Queue<T, N> {
     // according to the threadx docs, the supported messages sizes are 1 to 16 32 bit words
-    const SIZE_OK: () =
-        assert!(size_of::<T>() >= size_of::<u32>() && size_of::<T>() <= (size_of::<u32>() * 16));
+    const SIZE_OK: () = assert!(message_words::<T>() <= MAX_MESSAGE_WORDS);
+    // the owned storage must hold at least one message
+    const OWNED_OK: () = assert!(N > 0);
 
     pub const fn new() -> Self {
         let _ = Self::SIZE_OK;
-        Queue(core::mem::MaybeUninit::uninit(), core::marker::PhantomData)
+        Queue(
+            core::mem::MaybeUninit::uninit(),
+            Shared {
+                send_notify: NotifySlot::new(),
+                receiver_wakers: WakerSet::new(),
+                sender_wakers: WakerSet::new(),
+                deleted: AtomicBool::new(false),
+            },
+            UnsafeCell::new([const { MaybeUninit::uninit() }; N]),
+        )
     }
+
+    /// Create the queue in the storage owned by the queue, it holds exactly `N` messages.
+    pub fn initialize_owned(&'static mut self, name: &'static CStr) -> Result<(QueueSender<T>, QueueReceiver<T>), TxError> {
+        let _ = Self::OWNED_OK;
+        let storage = self.2.get();
+        // Safety: The storage is borrowed for 'static together with the queue and only accessed by ThreadX from here on.
+        let queue_memory = unsafe { core::slice::from_raw_parts_mut(storage.cast::<u8>(), size_of::<[Message<T>; N]>()) };
+        self.create(name, queue_memory)
+    }
+
     //TODO: Queue must not necessary live for 'static but can live as long as the memory block does
+    /// Create the queue in `queue_memory`, which must be ULONG aligned and hold at least one message. Messages take
+    /// `size_of::<T>()` rounded up to whole ULONG words.
     pub fn initialize(
         &'static mut self,
-        name: &CStr,
+        name: &'static CStr,
+        queue_memory: &'static mut [u8],
+    ) -> Result<(QueueSender<T>, QueueReceiver<T>), TxError> {
+        if !queue_memory.as_ptr().cast::<ULONG>().is_aligned() {
+            return Err(TxError::PtrError);
+        }
+        if queue_memory.len() < size_of::<Message<T>>() {
+            return Err(TxError::SizeError);
+        }
+        self.create(name, queue_memory)
+    }
+
+    fn create(
+        &'static mut self,
+        name: &'static CStr,
         queue_memory: &'static mut [u8],
     ) -> Result<(QueueSender<T>, QueueReceiver<T>), TxError> {
         let queue_ptr = self.0.as_mut_ptr();
@@ -50,7 +131,7 @@ impl<T: core::marker::Copy + 'static> Queue<T> {
         tx_checked_call!(_tx_queue_create(
             queue_ptr,
             name.as_ptr() as *mut i8,
-            size_of::<T>() as ULONG,
+            message_words::<T>() as ULONG,
             queue_memory.as_mut_ptr() as *mut core::ffi::c_void,
             queue_memory.len() as ULONG
         ))
@@ -63,42 +144,435 @@ impl<T: core::marker::Copy + 'static> Queue<T> {
     }
 }
 
-#[derive(Clone)]
 pub struct QueueSender<T>(*mut TX_QUEUE, core::marker::PhantomData<T>);
+
+impl<T> Clone for QueueSender<T> {
+    fn clone(&self) -> Self {
+        QueueSender(self.0, core::marker::PhantomData)
+    }
+}
 /// Safety: QueueSender is Sync and Send since the internal pointer is not exposed and the calls to send/sync
-/// can be done from any Thread as per ThreadX documentation.
+/// can be done from any Thread as per ThreadX documentation. Messages move between threads, hence `T` must be `Send`.
 
-unsafe impl<T> Send for QueueSender<T> {}
-unsafe impl<T> Sync for QueueSender<T> {}
+unsafe impl<T: Send> Send for QueueSender<T> {}
+unsafe impl<T: Send> Sync for QueueSender<T> {}
 
 /// Safety: QueueReceiver is Sync and Send since the internal pointer is not exposed and the calls to send/sync
-/// can be done from any Thread as per ThreadX documentation.
+/// can be done from any Thread as per ThreadX documentation. Messages move between threads, hence `T` must be `Send`.
 pub struct QueueReceiver<T>(*mut TX_QUEUE, core::marker::PhantomData<T>);
-unsafe impl<T> Send for QueueReceiver<T> {}
-unsafe impl<T> Sync for QueueReceiver<T> {}
+unsafe impl<T: Send> Send for QueueReceiver<T> {}
+unsafe impl<T: Send> Sync for QueueReceiver<T> {}
+
+/// The receiver passed to the closure registered with `set_send_notify`. It only lends out a `&QueueReceiver<T>`,
+/// so the closure cannot delete the queue.
+pub struct QueueReceiverRef<T>(QueueReceiver<T>);
+
+impl<T> core::ops::Deref for QueueReceiverRef<T> {
+    type Target = QueueReceiver<T>;
+
+    fn deref(&self) -> &QueueReceiver<T> {
+        &self.0
+    }
+}
+
+impl<T: Send + 'static> QueueSender<T> {
+    /// Fails with `TxError::QueueError` like ThreadX does for a deleted queue. The control block lives for 'static, so
+    /// a call racing with the deletion still accesses valid memory.
+    fn check_deleted(&self) -> Result<(), TxError> {
+        if shared::<T>(self.0).deleted.load(Ordering::Acquire) {
+            Err(TxError::QueueError)
+        } else {
+            Ok(())
+        }
+    }
 
-impl<T> QueueSender<T> {
-    pub fn send(&self, message: T, wait: WaitOption) -> Result<(), TxError> {
+    /// Send `message`, it is returned in the error if the queue stays full or the call fails otherwise. Fails with
+    /// `TxError::QueueError` once the queue was deleted.
+    pub fn send(&self, message: T, wait: WaitOption) -> Result<(), SendError<T>> {
+        if let Err(error) = self.check_deleted() {
+            return Err(SendError { message, error });
+        }
+        // ThreadX copies whole words, so the message is padded
+        let mut message = Message { value: ManuallyDrop::new(message) };
         let res = tx_checked_call!(_tx_queue_send(
             self.0,
-            &message as *const T as *mut core::ffi::c_void,
-            wait as ULONG
+            &mut message as *mut Message<T> as *mut core::ffi::c_void,
+            wait.into()
         ));
-        res
+        Self::sent(message, res.map_err(|e| wait.timed_out(e)))
     }
+
+    /// Like `send` but puts the message at the front of the queue so it is received next.
+    pub fn front_send(&self, message: T, wait: WaitOption) -> Result<(), SendError<T>> {
+        if let Err(error) = self.check_deleted() {
+            return Err(SendError { message, error });
+        }
+        let mut message = Message { value: ManuallyDrop::new(message) };
+        let res = tx_checked_call!(_tx_queue_front_send(
+            self.0,
+            &mut message as *mut Message<T> as *mut core::ffi::c_void,
+            wait.into()
+        ));
+        Self::sent(message, res.map_err(|e| wait.timed_out(e)))
+    }
+
+    /// Send `message` once there is room in the queue. Waits without blocking the thread, so it can be used by tasks of
+    /// the `Executor`. The task is woken when a message is received through a `QueueReceiver`. Fails with
+    /// `TxError::NotAvailable` if `MAX_ASYNC_WAITERS` other tasks are already waiting to send.
+    pub async fn send_async(&self, message: T) -> Result<(), SendError<T>> {
+        let mut message = Some(message);
+        // Removes the waker once the future completes or is dropped
+        let mut waiter = WakerRegistration::new(&shared::<T>(self.0).sender_wakers);
+        poll_fn(|cx| {
+            // Register first, so a message received in the meantime is not missed
+            if let Err(error) = waiter.register(cx.waker()) {
+                let message = message.take().expect("polled after completion");
+                return Poll::Ready(Err(SendError { message, error }));
+            }
+            if let Err(error) = self.check_deleted() {
+                let message = message.take().expect("polled after completion");
+                return Poll::Ready(Err(SendError { message, error }));
+            }
+            let mut pending = Message { value: ManuallyDrop::new(message.take().expect("polled after completion")) };
+            let res = tx_checked_call_no_log!(_tx_queue_send(
+                self.0,
+                &mut pending as *mut Message<T> as *mut core::ffi::c_void,
+                WaitOption::NoWait.into()
+            ));
+            match Self::sent(pending, res) {
+                Err(SendError { message: pending, error: TxError::QueueFull }) => {
+                    message = Some(pending);
+                    Poll::Pending
+                }
+                res => Poll::Ready(res),
+            }
+        })
+        .await
+    }
+
+    /// On success the copy in the queue owns the message and the `ManuallyDrop` keeps it from being dropped here.
+    fn sent(message: Message<T>, res: Result<(), TxError>) -> Result<(), SendError<T>> {
+        res.map_err(|error| SendError {
+            // Safety: ThreadX did not take the message, so it is still owned here.
+            message: ManuallyDrop::into_inner(unsafe { message.value }),
+            error,
+        })
+    }
+
+    /// Move the highest priority thread waiting for free space to the front of the suspension list.
+    pub fn prioritize(&self) -> Result<(), TxError> {
+        self.check_deleted()?;
+        tx_checked_call!(_tx_queue_prioritize(self.0))
+    }
+
+    /// Query name, number of messages and free space of the queue.
+    pub fn info(&self) -> Result<QueueInfo<'static>, TxError> {
+        self.check_deleted()?;
+        queue_info(self.0)
+    }
+
+    /// Query the performance counters of the queue.
+    pub fn performance(&self) -> Result<QueuePerformance, TxError> {
+        self.check_deleted()?;
+        queue_performance(self.0)
+    }
+}
+
+/// Snapshot of a queue returned by `QueueSender::info` and `QueueReceiver::info`.
+#[derive(Debug)]
+pub struct QueueInfo<'a> {
+    pub name: Option<&'a CStr>,
+    /// Number of messages in the queue.
+    pub enqueued: u32,
+    /// Number of messages the queue has room for.
+    pub available_storage: u32,
+    /// Number of threads waiting to send to or receive from the queue.
+    pub suspended_count: u32,
+}
+
+/// Counters returned by the `performance` functions of the queue handles and by `system_performance`.
+/// ThreadX must be built with `TX_QUEUE_ENABLE_PERFORMANCE_INFO`, otherwise `TxError::FeatureNotEnabled`
+/// is returned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueuePerformance {
+    pub messages_sent: u32,
+    pub messages_received: u32,
+    pub empty_suspensions: u32,
+    pub full_suspensions: u32,
+    pub full_errors: u32,
+    pub timeouts: u32,
+}
+
+impl From<[ULONG; 6]> for QueuePerformance {
+    fn from(perf: [ULONG; 6]) -> QueuePerformance {
+        let [messages_sent, messages_received, empty_suspensions, full_suspensions, full_errors, timeouts] = perf;
+        QueuePerformance { messages_sent, messages_received, empty_suspensions, full_suspensions, full_errors, timeouts }
+    }
+}
+
+/// Performance counters summed up over all queues.
+pub fn system_performance() -> Result<QueuePerformance, TxError> {
+    let mut perf = [0 as ULONG; 6];
+    let [sent, received, empty_suspensions, full_suspensions, full_errors, timeouts] = &mut perf;
+    tx_checked_call!(_tx_queue_performance_system_info_get(
+        sent,
+        received,
+        empty_suspensions,
+        full_suspensions,
+        full_errors,
+        timeouts
+    ))?;
+    Ok(QueuePerformance::from(perf))
+}
+
+fn queue_info(queue_ptr: *mut TX_QUEUE) -> Result<QueueInfo<'static>, TxError> {
+    let mut name: *mut CHAR = core::ptr::null_mut();
+    let mut enqueued: ULONG = 0;
+    let mut available_storage: ULONG = 0;
+    let mut first_suspended: *mut TX_THREAD = core::ptr::null_mut();
+    let mut suspended_count: ULONG = 0;
+    let mut next: *mut TX_QUEUE = core::ptr::null_mut();
+    tx_checked_call!(_tx_queue_info_get(
+        queue_ptr,
+        &mut name,
+        &mut enqueued,
+        &mut available_storage,
+        &mut first_suspended,
+        &mut suspended_count,
+        &mut next
+    ))?;
+    Ok(QueueInfo {
+        // Safety: ThreadX keeps the `'static` name passed to `initialize`.
+        name: (!name.is_null()).then(|| unsafe { CStr::from_ptr(name.cast()) }),
+        enqueued,
+        available_storage,
+        suspended_count,
+    })
+}
+
+fn queue_performance(queue_ptr: *mut TX_QUEUE) -> Result<QueuePerformance, TxError> {
+    let mut perf = [0 as ULONG; 6];
+    let [sent, received, empty_suspensions, full_suspensions, full_errors, timeouts] = &mut perf;
+    tx_checked_call!(_tx_queue_performance_info_get(
+        queue_ptr,
+        sent,
+        received,
+        empty_suspensions,
+        full_suspensions,
+        full_errors,
+        timeouts
+    ))?;
+    Ok(QueuePerformance::from(perf))
+}
+
+fn shared<T: Send + 'static>(queue_ptr: *mut TX_QUEUE) -> &'static Shared<T> {
+    // Safety: Handles are only created by `Queue::initialize` so the control block is embedded in a `Queue<T, N>`.
+    // The struct is `repr(C)` with the storage last, so the shared data has the same offset for every `N`.
+    unsafe { &(*(queue_ptr as *const Queue<T>)).1 }
 }
 
-impl<T> QueueReceiver<T> {
+/// Register the trampoline serving both `set_send_notify` and `receive_async`. It stays registered, an empty slot is skipped.
+fn enable_send_notify<T: Send + 'static>(queue_ptr: *mut TX_QUEUE) -> Result<(), TxError> {
+    tx_checked_call!(_tx_queue_send_notify(queue_ptr, Some(queue_send_notify_trampoline::<T>)))
+}
+
+unsafe extern "C" fn queue_send_notify_trampoline<T: Send + 'static>(queue_ptr: *mut TX_QUEUE) {
+    let shared = shared::<T>(queue_ptr);
+    shared.receiver_wakers.wake_all();
+    shared.send_notify.call(QueueReceiverRef(QueueReceiver(queue_ptr, core::marker::PhantomData)));
+}
+
+impl<T: Send + 'static> QueueReceiver<T> {
+    /// Register a closure called each time a message is sent to the queue, `None` removes it. The closure runs in the
+    /// context of the sender, possibly an ISR, see `NotifyFn`. ThreadX must be built without
+    /// `TX_DISABLE_NOTIFY_CALLBACKS`, otherwise `TxError::FeatureNotEnabled` is returned.
+    pub fn set_send_notify(&self, notify: Option<NotifyFn<QueueReceiverRef<T>>>) -> Result<(), TxError> {
+        shared::<T>(self.0).send_notify.set(notify);
+        enable_send_notify::<T>(self.0)
+    }
+
     pub fn receive(&self, wait: WaitOption) -> Result<T, TxError> {
-        let mut message = core::mem::MaybeUninit::uninit();
+        let mut message = core::mem::MaybeUninit::<Message<T>>::uninit();
         tx_checked_call_no_log!(_tx_queue_receive(
             self.0,
             message.as_mut_ptr() as *mut core::ffi::c_void,
-            wait as ULONG
+            wait.into()
+        ))
+        .map_err(|e| wait.timed_out(e))
+        .map(|_| {
+            // There is room for a waiting `send_async` now
+            shared::<T>(self.0).sender_wakers.wake_all();
+            unsafe {
+                //Safety: Message was initialized by ThreadX since the call returned successful.
+                ManuallyDrop::into_inner(message.assume_init().value)
+            }
+        })
+    }
+
+    /// Receive the next message. Waits without blocking the thread, so it can be used by tasks of the `Executor`.
+    /// The task is woken by the send notification, which runs in the context of the sender. If messages are sent from
+    /// an ISR, the waker must be safe to wake from there. Needs notify callbacks like `set_send_notify`. Fails with
+    /// `TxError::NotAvailable` if `MAX_ASYNC_WAITERS` other tasks are already waiting to receive.
+    pub async fn receive_async(&self) -> Result<T, TxError> {
+        enable_send_notify::<T>(self.0)?;
+        // Removes the waker once the future completes or is dropped
+        let mut waiter = WakerRegistration::new(&shared::<T>(self.0).receiver_wakers);
+        poll_fn(|cx| {
+            // Register first, so a message sent in the meantime is not missed
+            if let Err(e) = waiter.register(cx.waker()) {
+                return Poll::Ready(Err(e));
+            }
+            match self.receive(WaitOption::NoWait) {
+                Err(TxError::QueueEmpty) => Poll::Pending,
+                res => Poll::Ready(res),
+            }
+        })
+        .await
+    }
+
+    /// Drop all messages in the queue. Threads waiting to send are resumed.
+    pub fn flush(&self) -> Result<(), TxError> {
+        if core::mem::needs_drop::<T>() {
+            // A raw flush after draining would discard a message sent in between without dropping it. Receiving
+            // also resumes waiting senders, whose messages are dropped by the loop as well.
+            self.drain();
+            return Ok(());
+        }
+        tx_checked_call!(_tx_queue_flush(self.0))?;
+        shared::<T>(self.0).sender_wakers.wake_all();
+        Ok(())
+    }
+
+    /// ThreadX discards messages without dropping them, so they are received and dropped until the queue is empty.
+    fn drain(&self) {
+        if core::mem::needs_drop::<T>() {
+            while self.receive(WaitOption::NoWait).is_ok() {}
+        }
+    }
+
+    /// Move the highest priority thread waiting for a message to the front of the suspension list.
+    pub fn prioritize(&self) -> Result<(), TxError> {
+        tx_checked_call!(_tx_queue_prioritize(self.0))
+    }
+
+    /// Query name, number of messages and free space of the queue.
+    pub fn info(&self) -> Result<QueueInfo<'static>, TxError> {
+        queue_info(self.0)
+    }
+
+    /// Query the performance counters of the queue.
+    pub fn performance(&self) -> Result<QueuePerformance, TxError> {
+        queue_performance(self.0)
+    }
+
+    /// Delete the queue and drop the messages still in it. Threads waiting on it are resumed with `TxError::Deleted`,
+    /// remaining senders fail with `TxError::QueueError` afterwards.
+    pub fn delete(self) -> Result<(), TxError> {
+        let shared = shared::<T>(self.0);
+        // Stop senders first, so no message arrives after the queue was drained
+        shared.deleted.store(true, Ordering::Release);
+        self.drain();
+        if let Err(e) = tx_checked_call!(_tx_queue_delete(self.0)) {
+            shared.deleted.store(false, Ordering::Release);
+            return Err(e);
+        }
+        // Let pending async calls fail instead of waiting forever
+        shared.sender_wakers.wake_all();
+        shared.receiver_wakers.wake_all();
+        Ok(())
+    }
+}
+
+/// A failed send. Holds the message that could not be sent.
+pub struct SendError<T> {
+    pub message: T,
+    pub error: TxError,
+}
+
+impl<T> core::fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SendError").field("error", &self.error).finish_non_exhaustive()
+    }
+}
+
+/// Queue for messages of any size. Messages are moved into blocks of a block pool and only the
+/// `PoolBox` is passed through the ThreadX queue, so `T` is not limited to 64 bytes. The receiver
+/// gets the `PoolBox` which returns the block on drop. `T` must not require more than ULONG alignment,
+/// see `PoolBox`.
+pub struct PoolQueue<T: Send + 'static>(Queue<PoolBox<T>>);
+
+impl<T: Send + 'static> PoolQueue<T> {
+    pub const fn new() -> Self {
+        let _ = PoolBox::<T>::ALIGN_OK;
+        PoolQueue(Queue::new())
+    }
+
+    /// Create the queue. `pool` provides the message blocks, its block size must be at least `size_of::<T>()`.
+    pub fn initialize(
+        &'static mut self,
+        name: &'static CStr,
+        queue_memory: &'static mut [u8],
+        pool: BlockPoolHandle<'static>,
+    ) -> Result<(PoolQueueSender<T>, PoolQueueReceiver<T>), TxError> {
+        if pool.block_size() < size_of::<T>() {
+            return Err(TxError::SizeError);
+        }
+        let (sender, receiver) = self.0.initialize(name, queue_memory)?;
+        Ok((
+            PoolQueueSender {
+                sender,
+                pool: pool.as_ptr(),
+            },
+            PoolQueueReceiver(receiver),
         ))
-        .map(|_| unsafe {
-            //Safety: Message was initialized by ThreadX since the call returned successful.
-            message.assume_init()
+    }
+}
+
+pub struct PoolQueueSender<T> {
+    sender: QueueSender<PoolBox<T>>,
+    pool: *mut TX_BLOCK_POOL,
+}
+
+impl<T> Clone for PoolQueueSender<T> {
+    fn clone(&self) -> Self {
+        PoolQueueSender {
+            sender: self.sender.clone(),
+            pool: self.pool,
+        }
+    }
+}
+
+pub struct PoolQueueReceiver<T>(QueueReceiver<PoolBox<T>>);
+
+/// Safety: Messages are moved to the receiving thread, hence `T` must be `Send`. The pool is only used via
+/// ThreadX calls which can be done from any thread.
+unsafe impl<T: Send> Send for PoolQueueSender<T> {}
+unsafe impl<T: Send> Sync for PoolQueueSender<T> {}
+
+impl<T: Send + 'static> PoolQueueSender<T> {
+    /// Move `message` into a block of the pool. It can be filled in place and sent later with `send_box`.
+    pub fn allocate(&self, message: T, wait: WaitOption) -> Result<PoolBox<T>, SendError<T>> {
+        PoolBox::new_in(self.pool, message, wait).map_err(|(message, error)| SendError { message, error })
+    }
+
+    /// Send a message allocated with `allocate`. Only the pointer is copied.
+    pub fn send_box(&self, message: PoolBox<T>, wait: WaitOption) -> Result<(), SendError<PoolBox<T>>> {
+        self.sender.send(message, wait)
+    }
+
+    /// Allocate a block for `message` and send it. `wait` applies to both steps together.
+    pub fn send(&self, message: T, wait: WaitOption) -> Result<(), SendError<T>> {
+        let start = Instant::now();
+        let message = self.allocate(message, wait)?;
+        self.send_box(message, wait.remaining(start)).map_err(|e| SendError {
+            message: PoolBox::into_inner(e.message),
+            error: e.error,
         })
     }
 }
+
+impl<T: Send + 'static> PoolQueueReceiver<T> {
+    pub fn receive(&self, wait: WaitOption) -> Result<PoolBox<T>, TxError> {
+        self.0.receive(wait)
+    }
+}
@@ -0,0 +1,209 @@
+use core::cell::UnsafeCell;
+use core::ffi::CStr;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use crate::critical_section;
+use crate::mutex::{MutexError, RawMutex};
+use crate::semaphore::RawSemaphore;
+use crate::time::Instant;
+use crate::{tx_checked_call, tx_checked_call_no_log};
+
+use super::error::TxError;
+use super::WaitOption;
+use num_traits::FromPrimitive;
+use threadx_sys::_tx_semaphore_get;
+
+/// Reader-writer lock protecting a value of type `T`.
+///
+/// Any number of readers or a single writer can hold the lock. Writers are preferred: once a
+/// writer waits for the lock, new readers wait until the writer is done.
+///
+/// Writers hold a ThreadX mutex with priority inheritance while waiting for and holding the lock,
+/// so a low priority writer is boosted while it blocks higher priority threads. Readers only hold
+/// this mutex while entering. The last reader leaving signals a waiting writer via a semaphore.
+///
+/// Like `Mutex` the lock is const constructible, creates its ThreadX objects on first use and
+/// must be called from thread context. Locking again from a thread holding the write lock fails with
+/// `MutexError::AlreadyHeld`. A thread holding a read lock must not lock again, since a waiting
+/// writer would block it forever.
+pub struct RwLock<T> {
+    inner: UnsafeCell<T>,
+    // Held by writers, taken briefly by readers entering
+    writer: RawMutex,
+    // Signalled by the last reader leaving when a writer waits
+    readers_done: RawSemaphore,
+    // Only accessed within a critical section
+    state: UnsafeCell<State>,
+}
+
+struct State {
+    readers: u32,
+    writer_waiting: bool,
+}
+
+/// Safety: The inner value is only accessed according to the reader-writer protocol, `state` only
+/// within critical sections.
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T> RwLock<T> {
+    /// Creates an unnamed reader-writer lock.
+    pub const fn new(inner: T) -> RwLock<T> {
+        RwLock::with_name(inner, None)
+    }
+
+    /// Creates a reader-writer lock whose ThreadX objects carry `name`.
+    pub const fn with_name(inner: T, name: Option<&'static CStr>) -> RwLock<T> {
+        RwLock {
+            inner: UnsafeCell::new(inner),
+            writer: RawMutex::new(name, true),
+            readers_done: RawSemaphore::new(name),
+            state: UnsafeCell::new(State {
+                readers: 0,
+                writer_waiting: false,
+            }),
+        }
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        // Safety: `state` is only accessed with interrupts disabled.
+        critical_section(|| f(unsafe { &mut *self.state.get() }))
+    }
+
+    /// Locks the value for shared reading.
+    pub fn read(
+        &'static self,
+        wait_option: WaitOption,
+    ) -> Result<RwLockReadGuard<'static, T>, MutexError> {
+        if self.writer.held_by_current_thread() {
+            return Err(MutexError::AlreadyHeld);
+        }
+        self.writer.get(wait_option)?;
+        self.with_state(|state| state.readers += 1);
+        self.writer.put();
+        Ok(RwLockReadGuard {
+            lock: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Locks the value for reading without waiting. Returns `MutexError::WouldBlock` if a writer
+    /// holds or waits for the lock.
+    pub fn try_read(&'static self) -> Result<RwLockReadGuard<'static, T>, MutexError> {
+        self.read(WaitOption::NoWait)
+    }
+
+    /// Locks the value for exclusive writing.
+    pub fn write(
+        &'static self,
+        wait_option: WaitOption,
+    ) -> Result<RwLockWriteGuard<'static, T>, MutexError> {
+        if self.writer.held_by_current_thread() {
+            return Err(MutexError::AlreadyHeld);
+        }
+        let start = Instant::now();
+        let sem_ptr = self
+            .readers_done
+            .semaphore_ptr()
+            .map_err(MutexError::MutexError)?;
+        self.writer.get(wait_option)?;
+        // New readers are blocked from here on, wait for the current ones to leave
+        if !self.with_state(|state| {
+            state.writer_waiting = state.readers > 0;
+            state.writer_waiting
+        }) {
+            return Ok(RwLockWriteGuard {
+                lock: self,
+                _not_send: PhantomData,
+            });
+        }
+        let wait_option = wait_option.remaining(start);
+        let mut result = tx_checked_call_no_log!(_tx_semaphore_get(sem_ptr, wait_option.into()));
+        if result.is_err() {
+            // The last reader may have left in the meantime. Then the semaphore is put for us and
+            // has to be consumed.
+            if !self.with_state(|state| core::mem::take(&mut state.writer_waiting)) {
+                result =
+                    tx_checked_call!(_tx_semaphore_get(sem_ptr, WaitOption::WaitForever.into()));
+            }
+        }
+        match result {
+            Ok(_) => Ok(RwLockWriteGuard {
+                lock: self,
+                _not_send: PhantomData,
+            }),
+            Err(e) => {
+                self.writer.put();
+                Err(match wait_option {
+                    WaitOption::NoWait => MutexError::WouldBlock,
+                    _ => MutexError::MutexError(wait_option.timed_out(e)),
+                })
+            }
+        }
+    }
+
+    /// Locks the value for writing without waiting. Returns `MutexError::WouldBlock` if the lock is
+    /// held by anyone else.
+    pub fn try_write(&'static self) -> Result<RwLockWriteGuard<'static, T>, MutexError> {
+        self.write(WaitOption::NoWait)
+    }
+
+    /// Returns a mutable reference to the value. No locking is needed since the borrow is exclusive.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.inner.get() }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        let wake_writer = self.lock.with_state(|state| {
+            state.readers -= 1;
+            state.readers == 0 && core::mem::take(&mut state.writer_waiting)
+        });
+        if wake_writer {
+            // A waiting writer created the semaphore
+            if self.lock.readers_done.put().is_err() {
+                defmt::error!("RwLockReadGuard::drop failed to wake writer");
+            }
+        }
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.inner.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.inner.get() }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.writer.put();
+    }
+}
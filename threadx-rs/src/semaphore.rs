@@ -1,10 +1,11 @@
 
 use core::{mem::MaybeUninit, ffi::CStr};
-use crate::tx_checked_call;
+use core::cell::UnsafeCell;
+use crate::{tx_checked_call, CreateOnce, NotifyFn, NotifySlot};
 use super::{error::TxError, WaitOption};
 use defmt::error;
 use num_traits::FromPrimitive;
-use threadx_sys::{TX_SEMAPHORE, _tx_semaphore_create, _tx_semaphore_delete, _tx_semaphore_get, _tx_semaphore_put, _tx_semaphore_prioritize, _tx_semaphore_put_notify};
+use threadx_sys::{CHAR, TX_SEMAPHORE, TX_THREAD, ULONG, _tx_semaphore_ceiling_put, _tx_semaphore_info_get, _tx_semaphore_performance_info_get, _tx_semaphore_performance_system_info_get, _tx_semaphore_create, _tx_semaphore_delete, _tx_semaphore_get, _tx_semaphore_put, _tx_semaphore_prioritize, _tx_semaphore_put_notify};
 
 /*
 #define tx_semaphore_ceiling_put                    _tx_semaphore_ceiling_put
@@ -19,16 +20,34 @@ use threadx_sys::{TX_SEMAPHORE, _tx_semaphore_create, _tx_semaphore_delete, _tx_
 #define tx_semaphore_put_notify                     _tx_semaphore_put_notify
 */
 
-pub struct Semaphore(MaybeUninit<TX_SEMAPHORE>);
+/// The control block is the first field so the Rust side data can be found from the pointer ThreadX passes to callbacks.
+/// The third field is the ceiling of a bounded semaphore, it is only written before the semaphore is created.
+#[repr(C)]
+pub struct Semaphore(MaybeUninit<TX_SEMAPHORE>, NotifySlot<SemaphoreUserHandle>, Option<u32>);
 
 impl Semaphore {
     pub const fn new() -> Self {
-        Semaphore(MaybeUninit::<TX_SEMAPHORE>::uninit())
+        Semaphore(MaybeUninit::<TX_SEMAPHORE>::uninit(), NotifySlot::new(), None)
+    }
+
+    /// Create a semaphore whose count never exceeds `ceiling`. `put` fails with `TxError::CeilingExceeded`
+    /// instead, which catches permits returned twice, e.g. for resource pools.
+    pub fn initialize_bounded(
+        &'static mut self,
+        name: &'static CStr,
+        initial_count: u32,
+        ceiling: u32,
+    ) -> Result<SemaphoreOwnerHandle, TxError> {
+        if ceiling == 0 || initial_count > ceiling {
+            return Err(TxError::InvalidCeiling);
+        }
+        self.2 = Some(ceiling);
+        self.initialize(name, initial_count)
     }
 
     pub fn initialize(
         &'static mut self,
-        name: &CStr,
+        name: &'static CStr,
         initial_count: u32,
     ) -> Result<SemaphoreOwnerHandle, TxError> {
         let sem_ptr = self.0.as_mut_ptr();
@@ -51,8 +70,69 @@ impl Semaphore {
 
 #[derive(Clone,Copy)]
 pub struct SemaphoreOwnerHandle(*mut TX_SEMAPHORE);
+#[derive(Clone,Copy)]
 pub struct SemaphoreUserHandle(*mut TX_SEMAPHORE);
 
+/// Safety: The handles only give access to ThreadX semaphore services, which can be called from any thread.
+unsafe impl Send for SemaphoreOwnerHandle {}
+unsafe impl Sync for SemaphoreOwnerHandle {}
+unsafe impl Send for SemaphoreUserHandle {}
+unsafe impl Sync for SemaphoreUserHandle {}
+
+/// Snapshot of a semaphore returned by `SemaphoreUser::info`.
+#[derive(Debug)]
+pub struct SemaphoreInfo<'a> {
+    pub name: Option<&'a CStr>,
+    pub count: u32,
+    /// Number of threads waiting for the semaphore.
+    pub suspended_count: u32,
+}
+
+/// Counters returned by `SemaphoreUser::performance` and `system_performance`.
+/// ThreadX must be built with `TX_SEMAPHORE_ENABLE_PERFORMANCE_INFO`, otherwise
+/// `TxError::FeatureNotEnabled` is returned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SemaphorePerformance {
+    pub puts: u32,
+    pub gets: u32,
+    pub suspensions: u32,
+    pub timeouts: u32,
+}
+
+impl From<[ULONG; 4]> for SemaphorePerformance {
+    fn from(perf: [ULONG; 4]) -> SemaphorePerformance {
+        let [puts, gets, suspensions, timeouts] = perf;
+        SemaphorePerformance { puts, gets, suspensions, timeouts }
+    }
+}
+
+/// Performance counters summed up over all semaphores.
+pub fn system_performance() -> Result<SemaphorePerformance, TxError> {
+    let mut perf = [0 as ULONG; 4];
+    let [puts, gets, suspensions, timeouts] = &mut perf;
+    tx_checked_call!(_tx_semaphore_performance_system_info_get(puts, gets, suspensions, timeouts))?;
+    Ok(SemaphorePerformance::from(perf))
+}
+
+/// A count taken from a semaphore with `SemaphoreUser::acquire`. It is put back on drop.
+#[must_use = "the permit is returned immediately if it is not used"]
+pub struct Permit(SemaphoreUserHandle);
+
+impl Permit {
+    /// Keep the count taken from the semaphore.
+    pub fn forget(self) {
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.0.put().is_err() {
+            error!("Permit::drop failed to put semaphore");
+        }
+    }
+}
+
 pub trait SemaphoreOwner {
     fn delete(self) -> Result<(),TxError> ;
     fn get_semaphore_user(&self) -> SemaphoreUserHandle;
@@ -60,9 +140,29 @@ pub trait SemaphoreOwner {
 
 pub trait SemaphoreUser {
     fn get(&self, wait: WaitOption) -> Result<(), TxError>;
+    /// Take a count which is put back when the returned `Permit` is dropped.
+    fn acquire(&self, wait: WaitOption) -> Result<Permit, TxError>;
+    /// Increment the count. Bounded semaphores fail with `TxError::CeilingExceeded` instead of exceeding their ceiling.
     fn put(&self) -> Result<(), TxError>;
+    /// Increment the count unless it already reached `ceiling`.
+    fn ceiling_put(&self, ceiling: u32) -> Result<(), TxError>;
     fn prioritize(&self) -> Result<(), TxError>;
-    fn semaphore_put_notify(&self, notify: fn(SemaphoreUserHandle)) -> Result<(), TxError>;
+    /// Query name, count and number of waiting threads.
+    fn info(&self) -> Result<SemaphoreInfo<'static>, TxError>;
+    /// The current count.
+    fn available(&self) -> Result<u32, TxError> {
+        self.info().map(|info| info.count)
+    }
+    /// Number of threads waiting for the semaphore.
+    fn suspended_count(&self) -> Result<u32, TxError> {
+        self.info().map(|info| info.suspended_count)
+    }
+    /// Query the performance counters of the semaphore.
+    fn performance(&self) -> Result<SemaphorePerformance, TxError>;
+    /// Register a closure called each time the semaphore is put, `None` removes it. The closure runs in the
+    /// context of the caller of `put`, possibly an ISR, see `NotifyFn`. ThreadX must be built without
+    /// `TX_DISABLE_NOTIFY_CALLBACKS`, otherwise `TxError::FeatureNotEnabled` is returned.
+    fn semaphore_put_notify(&self, notify: Option<NotifyFn<SemaphoreUserHandle>>) -> Result<(), TxError>;
 }
 
 impl SemaphoreOwnerHandle {
@@ -85,12 +185,28 @@ impl SemaphoreUser for SemaphoreUserHandle {
     fn get(&self, wait: WaitOption) -> Result<(), TxError> {
         tx_checked_call!(_tx_semaphore_get(
             self.0,
-            wait as u32
+            wait.into()
         ))
+        .map_err(|e| wait.timed_out(e))
     }
+
+    fn acquire(&self, wait: WaitOption) -> Result<Permit, TxError> {
+        self.get(wait).map(|_| Permit(*self))
+    }
+
     fn put(&self) -> Result<(), TxError> {
-        tx_checked_call!(_tx_semaphore_put(
-            self.0
+        match self.semaphore().2 {
+            Some(ceiling) => self.ceiling_put(ceiling),
+            None => tx_checked_call!(_tx_semaphore_put(
+                self.0
+            )),
+        }
+    }
+
+    fn ceiling_put(&self, ceiling: u32) -> Result<(), TxError> {
+        tx_checked_call!(_tx_semaphore_ceiling_put(
+            self.0,
+            ceiling
         ))
     }
 
@@ -100,27 +216,98 @@ impl SemaphoreUser for SemaphoreUserHandle {
         ))
     }
 
-    fn semaphore_put_notify(&self, notify: fn(SemaphoreUserHandle)) -> Result<(), TxError> {
-        let trampoline = get_notify_trampoline(&notify);
+    fn info(&self) -> Result<SemaphoreInfo<'static>, TxError> {
+        let mut name: *mut CHAR = core::ptr::null_mut();
+        let mut count: ULONG = 0;
+        let mut first_suspended: *mut TX_THREAD = core::ptr::null_mut();
+        let mut suspended_count: ULONG = 0;
+        let mut next: *mut TX_SEMAPHORE = core::ptr::null_mut();
+        tx_checked_call!(_tx_semaphore_info_get(
+            self.0,
+            &mut name,
+            &mut count,
+            &mut first_suspended,
+            &mut suspended_count,
+            &mut next
+        ))?;
+        Ok(SemaphoreInfo {
+            // Safety: ThreadX keeps the `'static` name passed to `initialize`.
+            name: (!name.is_null()).then(|| unsafe { CStr::from_ptr(name.cast()) }),
+            count,
+            suspended_count,
+        })
+    }
+
+    fn performance(&self) -> Result<SemaphorePerformance, TxError> {
+        let mut perf = [0 as ULONG; 4];
+        let [puts, gets, suspensions, timeouts] = &mut perf;
+        tx_checked_call!(_tx_semaphore_performance_info_get(self.0, puts, gets, suspensions, timeouts))?;
+        Ok(SemaphorePerformance::from(perf))
+    }
+
+    fn semaphore_put_notify(&self, notify: Option<NotifyFn<SemaphoreUserHandle>>) -> Result<(), TxError> {
+        self.semaphore().1.set(notify);
         tx_checked_call!(_tx_semaphore_put_notify(
             self.0,
-            Some(trampoline)
+            notify.map(|_| semaphore_put_notify_trampoline as unsafe extern "C" fn(*mut TX_SEMAPHORE))
         ))
     }
 }
 
-type SemaphoreNotifyCallback = unsafe extern "C" fn(*mut TX_SEMAPHORE);
+impl SemaphoreUserHandle {
+    fn semaphore(&self) -> &'static Semaphore {
+        // Safety: Handles are only created by `Semaphore::initialize` so the control block is embedded in a `Semaphore`.
+        unsafe { &*(self.0 as *const Semaphore) }
+    }
+}
 
-unsafe extern "C" fn semaphore_cb_trampoline<F>(arg: *mut TX_SEMAPHORE)
-where F: Fn(SemaphoreUserHandle)
-{
-    let closure = &mut *(arg as *mut F);
-    closure(SemaphoreUserHandle(arg));
+unsafe extern "C" fn semaphore_put_notify_trampoline(sem_ptr: *mut TX_SEMAPHORE) {
+    // Safety: The notification is only registered for semaphores embedded in a `Semaphore`.
+    let semaphore = &*(sem_ptr as *const Semaphore);
+    semaphore.1.call(SemaphoreUserHandle(sem_ptr));
 }
 
-fn get_notify_trampoline<F>(_: &F) -> SemaphoreNotifyCallback
-where
-    F: Fn(SemaphoreUserHandle),
-{
-    semaphore_cb_trampoline::<F>
+/// A ThreadX semaphore created on first use. Building block for the lock types of this crate.
+pub(crate) struct RawSemaphore {
+    semaphore: UnsafeCell<MaybeUninit<TX_SEMAPHORE>>,
+    created: CreateOnce,
+    name: Option<&'static CStr>,
+}
+
+/// Safety: The control block is created exactly once, claimed through `CreateOnce`, afterwards it is
+/// only accessed via ThreadX calls.
+unsafe impl Send for RawSemaphore {}
+unsafe impl Sync for RawSemaphore {}
+
+impl RawSemaphore {
+    pub(crate) const fn new(name: Option<&'static CStr>) -> RawSemaphore {
+        RawSemaphore {
+            semaphore: UnsafeCell::new(MaybeUninit::uninit()),
+            created: CreateOnce::new(),
+            name,
+        }
+    }
+
+    /// Returns the control block, creating the semaphore with a count of 0 on first call.
+    pub(crate) fn semaphore_ptr(&'static self) -> Result<*mut TX_SEMAPHORE, TxError> {
+        let sem_ptr: *mut TX_SEMAPHORE = self.semaphore.get().cast();
+        self.created.call(|| {
+            let name = self.name.map_or(core::ptr::null_mut(), |n| n.as_ptr() as *mut CHAR);
+            tx_checked_call!(_tx_semaphore_create(sem_ptr, name, 0))
+        })?;
+        Ok(sem_ptr)
+    }
+
+    /// Increments the semaphore. Must only be called after it was created by `semaphore_ptr`.
+    pub(crate) fn put(&self) -> Result<(), TxError> {
+        tx_checked_call!(_tx_semaphore_put(self.semaphore.get().cast()))
+    }
+}
+
+impl Drop for RawSemaphore {
+    fn drop(&mut self) {
+        if self.created.is_created() {
+            let _ = tx_checked_call!(_tx_semaphore_delete(self.semaphore.get().cast()));
+        }
+    }
 }
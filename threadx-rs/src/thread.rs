@@ -1,51 +1,404 @@
+use core::cell::UnsafeCell;
 use core::ffi::CStr;
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicPtr, Ordering};
 use core::time::Duration;
 
-use threadx_sys::{_tx_thread_create, _tx_thread_resume, TX_THREAD, ULONG};
-use threadx_sys::{_tx_thread_delete, _tx_thread_sleep, _tx_thread_suspend};
+use threadx_sys::_tx_byte_release;
+use threadx_sys::_tx_thread_stack_error_notify;
+#[cfg(feature = "alloc")]
+use threadx_sys::{_tx_event_flags_create, _tx_event_flags_delete, _tx_event_flags_get};
+#[cfg(feature = "alloc")]
+use threadx_sys::{_tx_event_flags_set, TX_AND, TX_COMPLETED, TX_EVENT_FLAGS_GROUP, TX_OR};
+use threadx_sys::{_tx_thread_create, _tx_thread_resume, TX_THREAD, UINT, ULONG};
+use threadx_sys::{_tx_thread_delete, _tx_thread_sleep, _tx_thread_suspend, _tx_thread_terminate};
+#[cfg(feature = "alloc")]
+use threadx_sys::{_tx_thread_entry_exit_notify, TX_TERMINATED, TX_THREAD_ENTRY};
+use threadx_sys::{_tx_thread_identify, _tx_thread_info_get, CHAR, TX_MAX_PRIORITIES};
+use threadx_sys::{
+    _tx_thread_preemption_change, _tx_thread_priority_change, _tx_thread_relinquish,
+};
+use threadx_sys::{_tx_thread_reset, _tx_thread_time_slice_change, _tx_thread_wait_abort};
 
-use crate::time::TxTicks;
+use crate::pool::BytePoolHandle;
+use crate::time::{Instant, TxTicks};
 use crate::tx_checked_call;
+#[cfg(feature = "alloc")]
+use crate::tx_checked_call_no_log;
+use crate::WaitOption;
+use crate::critical_section;
 
 use super::error::TxError;
+#[cfg(feature = "alloc")]
 use defmt::error;
+use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
 pub struct Thread {
     tx_struct: MaybeUninit<TX_THREAD>,
+    // ThreadX only keeps a pointer to the name so it has to live as long as the thread.
+    name: [u8; 32],
+    ext: ThreadExt,
+    #[cfg(feature = "alloc")]
+    boxed_entry: UnsafeCell<Option<alloc::boxed::Box<dyn FnOnce()>>>,
 }
 
-pub struct ThreadHandle {
+/// Thread that stores its entry closure inline instead of boxing it, so it
+/// can be used without a global allocator. Like `Thread` it is meant to be
+/// placed in a static. The closure type is erased, `SIZE` is the number of
+/// bytes reserved for the closure and its captures. A closure that is larger
+/// or needs an alignment above 8 bytes is rejected at compile time.
+///
+/// ```ignore
+/// static WORKER: StaticCell<StaticThread<16>> = StaticCell::new();
+/// let sender = queue_sender.clone();
+/// WORKER
+///     .init(StaticThread::new())
+///     .initialize("worker", move || do_work(sender), stack, 4, 4, 0)?;
+/// ```
+pub struct StaticThread<const SIZE: usize> {
+    thread: Thread,
+    entry: InlineEntry<SIZE>,
+}
+
+/// Largest closure alignment a `StaticThread` supports.
+const INLINE_ENTRY_ALIGN: usize = 8;
+
+#[repr(C, align(8))]
+struct InlineStorage<const SIZE: usize>([MaybeUninit<u8>; SIZE]);
+
+/// Type erased closure stored in a `StaticThread`.
+struct InlineEntry<const SIZE: usize> {
+    storage: UnsafeCell<InlineStorage<SIZE>>,
+    // Runs respectively drops the closure in `storage`, `None` if it is empty
+    vtable: UnsafeCell<Option<InlineVtable>>,
+}
+
+#[derive(Clone, Copy)]
+struct InlineVtable {
+    run: unsafe fn(*mut u8),
+    drop: unsafe fn(*mut u8),
+}
+
+/// Safety: Only closures that are `Send` are stored.
+unsafe impl<const SIZE: usize> Send for InlineEntry<SIZE> {}
+
+unsafe fn run_inline<F: FnOnce()>(ptr: *mut u8) {
+    ptr.cast::<F>().read()()
+}
+
+unsafe fn drop_inline<F>(ptr: *mut u8) {
+    ptr.cast::<F>().drop_in_place()
+}
+
+#[cfg(feature = "alloc")]
+type EntryExitNotifyCb = alloc::boxed::Box<dyn Fn(ThreadEvent) + Send + Sync>;
+
+/// Rust side data of a thread created from a closure. Its address is passed
+/// to ThreadX as the entry parameter so it can be found from the control block.
+struct ThreadExt {
+    // Points to the slot holding the closure, inside the `Thread` or `StaticThread`.
+    entry: UnsafeCell<Option<NonNull<dyn Entry>>>,
+    #[cfg(feature = "alloc")]
+    entry_exit_notify: UnsafeCell<Option<EntryExitNotifyCb>>,
+    // The stack was allocated from a byte pool and is released on delete
+    pool_stack: UnsafeCell<bool>,
+    // Values of `thread_local!` variables, only accessed by the thread itself.
+    #[cfg(feature = "alloc")]
+    locals: UnsafeCell<Option<alloc::boxed::Box<LocalNode>>>,
+}
+
+impl ThreadExt {
+    const fn new() -> ThreadExt {
+        ThreadExt {
+            entry: UnsafeCell::new(None),
+            #[cfg(feature = "alloc")]
+            entry_exit_notify: UnsafeCell::new(None),
+            pool_stack: UnsafeCell::new(false),
+            #[cfg(feature = "alloc")]
+            locals: UnsafeCell::new(None),
+        }
+    }
+}
+
+/// Slot holding the entry closure of a thread, lets the trampoline run the
+/// closure without knowing its type.
+trait Entry {
+    /// Safety: Must only be called by the thread owning the slot.
+    unsafe fn run(&self);
+    /// Safety: The thread owning the slot must not run.
+    unsafe fn clear(&self);
+}
+
+impl<const SIZE: usize> InlineEntry<SIZE> {
+    const fn new() -> InlineEntry<SIZE> {
+        InlineEntry {
+            storage: UnsafeCell::new(InlineStorage([MaybeUninit::uninit(); SIZE])),
+            vtable: UnsafeCell::new(None),
+        }
+    }
+
+    fn set<F: FnOnce() + Send + 'static>(&mut self, f: F) {
+        const {
+            assert!(
+                size_of::<F>() <= SIZE,
+                "closure does not fit into the StaticThread, increase SIZE"
+            );
+            assert!(align_of::<F>() <= INLINE_ENTRY_ALIGN);
+        }
+        // Safety: Exclusively borrowed, so the thread does not run.
+        unsafe { self.clear() };
+        // Safety: The storage is large and aligned enough for `F`, checked above.
+        unsafe { self.storage.get_mut().0.as_mut_ptr().cast::<F>().write(f) };
+        *self.vtable.get_mut() = Some(InlineVtable {
+            run: run_inline::<F>,
+            drop: drop_inline::<F>,
+        });
+    }
+}
+
+impl<const SIZE: usize> Entry for InlineEntry<SIZE> {
+    unsafe fn run(&self) {
+        if let Some(vtable) = (*self.vtable.get()).take() {
+            (vtable.run)((*self.storage.get()).0.as_mut_ptr().cast());
+        }
+    }
+
+    unsafe fn clear(&self) {
+        if let Some(vtable) = (*self.vtable.get()).take() {
+            (vtable.drop)((*self.storage.get()).0.as_mut_ptr().cast());
+        }
+    }
+}
+
+impl<F: FnOnce()> Entry for UnsafeCell<Option<F>> {
+    unsafe fn run(&self) {
+        if let Some(entry_function) = (*self.get()).take() {
+            entry_function();
+        }
+    }
+
+    unsafe fn clear(&self) {
+        drop((*self.get()).take());
+    }
+}
+
+/// Reported to the entry/exit notification of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadEvent {
+    /// The thread is about to call its entry function.
+    Entry,
+    /// The entry function returned or the thread was terminated.
+    Exit,
+}
+
+/// Handle to a created thread. The type parameter tracks the state the
+/// thread is known to be in, so only the transitions that are valid for
+/// that state are available.
+pub struct ThreadHandle<S = Running> {
     tx_ptr: *mut TX_THREAD,
+    state: PhantomData<S>,
 }
 
+/// Safety: The handle only passes the pointer to the ThreadX thread services
+/// which can be called from any thread.
+unsafe impl<S> Send for ThreadHandle<S> {}
+unsafe impl<S> Sync for ThreadHandle<S> {}
+
 pub struct UnInitialized;
 pub struct Running;
 pub struct Suspended;
 pub struct Stopped;
 
+/// Thread priority. 0 is the highest priority and `TX_MAX_PRIORITIES - 1`
+/// the lowest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Priority(u32);
+
+impl Priority {
+    pub const fn new(priority: u32) -> Result<Priority, TxError> {
+        if priority >= TX_MAX_PRIORITIES {
+            return Err(TxError::PriorityError);
+        }
+        Ok(Priority(priority))
+    }
+}
+
+impl TryFrom<u32> for Priority {
+    type Error = TxError;
+
+    fn try_from(priority: u32) -> Result<Self, Self::Error> {
+        Priority::new(priority)
+    }
+}
+
+impl From<Priority> for u32 {
+    fn from(priority: Priority) -> u32 {
+        priority.0
+    }
+}
+
+/// Preemption threshold of a thread. Only threads with a priority higher
+/// (numerically lower) than the threshold may preempt the thread. The
+/// threshold must not be lower than the priority of the thread it is used
+/// with, a threshold equal to the priority disables preemption threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PreemptThreshold(u32);
+
+impl PreemptThreshold {
+    pub const fn new(threshold: u32, priority: Priority) -> Result<PreemptThreshold, TxError> {
+        if threshold >= TX_MAX_PRIORITIES {
+            return Err(TxError::PriorityError);
+        }
+        if threshold > priority.0 {
+            return Err(TxError::ThreshError);
+        }
+        Ok(PreemptThreshold(threshold))
+    }
+
+    /// Threshold that disables preemption threshold for a thread of the given priority.
+    pub const fn disabled(priority: Priority) -> PreemptThreshold {
+        PreemptThreshold(priority.0)
+    }
+}
+
+impl From<PreemptThreshold> for u32 {
+    fn from(threshold: PreemptThreshold) -> u32 {
+        threshold.0
+    }
+}
+
+/// Execution state of a thread as reported by ThreadX. All states from
+/// `Suspended` onwards also tell what the thread is suspended on.
+#[repr(u32)]
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    Ready = threadx_sys::TX_READY,
+    Completed = threadx_sys::TX_COMPLETED,
+    Terminated = threadx_sys::TX_TERMINATED,
+    Suspended = threadx_sys::TX_SUSPENDED,
+    Sleep = threadx_sys::TX_SLEEP,
+    QueueSuspended = threadx_sys::TX_QUEUE_SUSP,
+    SemaphoreSuspended = threadx_sys::TX_SEMAPHORE_SUSP,
+    EventFlagSuspended = threadx_sys::TX_EVENT_FLAG,
+    BlockMemorySuspended = threadx_sys::TX_BLOCK_MEMORY,
+    ByteMemorySuspended = threadx_sys::TX_BYTE_MEMORY,
+    IoDriverSuspended = threadx_sys::TX_IO_DRIVER,
+    FileSuspended = threadx_sys::TX_FILE,
+    TcpIpSuspended = threadx_sys::TX_TCP_IP,
+    MutexSuspended = threadx_sys::TX_MUTEX_SUSP,
+    PriorityChange = threadx_sys::TX_PRIORITY_CHANGE,
+}
+
+impl ThreadState {
+    pub fn is_suspended(&self) -> bool {
+        *self as u32 >= threadx_sys::TX_SUSPENDED
+    }
+}
+
+/// Snapshot of a thread returned by `ThreadHandle::info`.
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadInfo<'a> {
+    pub name: Option<&'a CStr>,
+    pub state: ThreadState,
+    pub run_count: u32,
+    pub priority: Priority,
+    pub preempt_threshold: PreemptThreshold,
+    pub time_slice: u32,
+}
+
 impl Thread {
     pub const fn new() -> Thread {
         Thread {
             tx_struct: core::mem::MaybeUninit::uninit(),
+            name: [0u8; 32],
+            ext: ThreadExt::new(),
+            #[cfg(feature = "alloc")]
+            boxed_entry: UnsafeCell::new(None),
         }
     }
 }
 
+impl<const SIZE: usize> StaticThread<SIZE> {
+    pub const fn new() -> StaticThread<SIZE> {
+        StaticThread {
+            thread: Thread::new(),
+            entry: InlineEntry::new(),
+        }
+    }
+
+    /// Create the thread and start it immediately.
+    pub fn initialize<F: FnOnce() + Send + 'static>(
+        &'static mut self,
+        name: &str,
+        entry_function: F,
+        stack: &'static mut [u8],
+        priority: u32,
+        preempt_threshold: u32,
+        time_slice: u32,
+    ) -> Result<ThreadHandle<Running>, TxError> {
+        self.entry.set(entry_function);
+        let entry = NonNull::from(&self.entry);
+        self.thread
+            .create(
+                name,
+                entry,
+                stack,
+                priority,
+                preempt_threshold,
+                time_slice,
+                true,
+            )
+            .map(ThreadHandle::new)
+    }
+}
+
 fn __threadx_fn(_val: u32) {
     todo!()
 }
 
-unsafe extern "C" fn thread_box_callback_trampoline(arg: ULONG) {
-    let argc: *mut alloc::boxed::Box<dyn FnOnce()> =
-        core::ptr::with_exposed_provenance_mut(arg as usize);
-    (argc.read())();
+// arg is the address of the `ThreadExt` of the thread. The closure is taken
+// out of its slot so a thread that was reset and started again completes
+// immediately instead of calling it twice.
+unsafe extern "C" fn thread_entry_trampoline(arg: ULONG) {
+    let ext: *const ThreadExt = core::ptr::with_exposed_provenance(arg as usize);
+    if let Some(entry) = *(*ext).entry.get() {
+        entry.as_ref().run();
+        // Thread locals are dropped on the thread that created them
+        #[cfg(feature = "alloc")]
+        drop((*(*ext).locals.get()).take());
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe extern "C" fn entry_exit_notify_trampoline(thread_ptr: *mut TX_THREAD, type_: UINT) {
+    let thread: ThreadHandle<Running> = ThreadHandle::new(thread_ptr);
+    let event = if type_ == TX_THREAD_ENTRY {
+        ThreadEvent::Entry
+    } else {
+        ThreadEvent::Exit
+    };
+    // Safety: The closure is only replaced while the thread is suspended and only
+    // called through a shared reference, since the exit notification of a
+    // terminating thread may run while the thread itself is still in its entry
+    // notification.
+    if let Some(notify) = thread
+        .ext()
+        .and_then(|ext| (*ext.entry_exit_notify.get()).as_ref())
+    {
+        notify(event);
+    }
 }
 
 impl Thread {
+    /// Create the thread and start it immediately.
+    #[cfg(feature = "alloc")]
     pub fn initialize_with_autostart_box(
         &'static mut self,
         name: &str,
@@ -54,31 +407,138 @@ impl Thread {
         priority: u32,
         preempt_threshold: u32,
         time_slice: u32,
-    ) -> Result<ThreadHandle, TxError> {
-        let expiration_function_ptr =
-            alloc::boxed::Box::into_raw(alloc::boxed::Box::new(entry_function));
+    ) -> Result<ThreadHandle<Running>, TxError> {
+        self.create_box(
+            name,
+            entry_function,
+            stack,
+            priority,
+            preempt_threshold,
+            time_slice,
+            true,
+        )
+        .map(ThreadHandle::new)
+    }
+
+    /// Create the thread without starting it. Use `ThreadHandle::start` to
+    /// let the thread run.
+    #[cfg(feature = "alloc")]
+    pub fn initialize_box(
+        &'static mut self,
+        name: &str,
+        entry_function: alloc::boxed::Box<dyn FnOnce()>,
+        stack: &'static mut [u8],
+        priority: u32,
+        preempt_threshold: u32,
+        time_slice: u32,
+    ) -> Result<ThreadHandle<Suspended>, TxError> {
+        self.create_box(
+            name,
+            entry_function,
+            stack,
+            priority,
+            preempt_threshold,
+            time_slice,
+            false,
+        )
+        .map(ThreadHandle::new)
+    }
+
+    /// Create and start a thread whose return value can be retrieved with
+    /// the returned `JoinHandle`.
+    #[cfg(feature = "alloc")]
+    pub fn initialize_joinable<F, T>(
+        &'static mut self,
+        name: &str,
+        entry_function: F,
+        stack: &'static mut [u8],
+        priority: u32,
+        preempt_threshold: u32,
+        time_slice: u32,
+    ) -> Result<JoinHandle<T>, TxError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let packet = Packet::new()?;
+        let their_packet = packet.clone();
+        let main = alloc::boxed::Box::new(move || {
+            let result = entry_function();
+            their_packet.complete(result);
+        });
+        let thread = self.initialize_with_autostart_box(
+            name,
+            main,
+            stack,
+            priority,
+            preempt_threshold,
+            time_slice,
+        )?;
+        Ok(JoinHandle { thread, packet })
+    }
+
+    // The caller has to ensure that the stack outlives the thread.
+    #[cfg(feature = "alloc")]
+    fn create_box(
+        &mut self,
+        name: &str,
+        entry_function: alloc::boxed::Box<dyn FnOnce()>,
+        stack: &mut [u8],
+        priority: u32,
+        preempt_threshold: u32,
+        time_slice: u32,
+        auto_start: bool,
+    ) -> Result<*mut TX_THREAD, TxError> {
+        *self.boxed_entry.get_mut() = Some(entry_function);
+        let entry = NonNull::from(&self.boxed_entry);
+        self.create(
+            name,
+            entry,
+            stack,
+            priority,
+            preempt_threshold,
+            time_slice,
+            auto_start,
+        )
+    }
+
+    // The caller has to ensure that the entry slot and the stack outlive the thread.
+    fn create(
+        &mut self,
+        name: &str,
+        entry: NonNull<dyn Entry>,
+        stack: &mut [u8],
+        priority: u32,
+        preempt_threshold: u32,
+        time_slice: u32,
+        auto_start: bool,
+    ) -> Result<*mut TX_THREAD, TxError> {
+        let priority = Priority::new(priority)?;
+        let preempt_threshold = PreemptThreshold::new(preempt_threshold, priority)?;
+        // Leave room for the NUL terminator
+        if name.len() >= self.name.len() {
+            return Err(TxError::NameTooLong);
+        }
+        *self.ext.entry.get_mut() = Some(entry);
         //convert to a ULONG
-        let entry_function_addr = expiration_function_ptr.expose_provenance() as ULONG;
-        // Check that strlen < 31
-        let mut local_name = [0u8; 32];
-        local_name[..name.len()].copy_from_slice(name.as_bytes());
+        let ext_addr = (&raw const self.ext).expose_provenance() as ULONG;
+        self.name = [0; 32];
+        self.name[..name.len()].copy_from_slice(name.as_bytes());
 
         tx_checked_call!(_tx_thread_create(
             // TODO: Ensure that threadx api does not modify this
             self.tx_struct.as_mut_ptr(),
-            local_name.as_mut_ptr() as *mut i8,
-            Some(thread_box_callback_trampoline),
-            entry_function_addr,
+            self.name.as_mut_ptr() as *mut i8,
+            Some(thread_entry_trampoline),
+            ext_addr,
             stack.as_mut_ptr() as *mut core::ffi::c_void,
             stack.len() as ULONG,
-            priority as ULONG,
-            preempt_threshold as ULONG,
+            priority.into(),
+            preempt_threshold.into(),
             time_slice as ULONG,
-            1
+            if auto_start { 1 } else { 0 }
         ))
-        .map(|_| ThreadHandle {
-            tx_ptr: self.tx_struct.as_mut_ptr(),
-        })
+        .map(|_| self.tx_struct.as_mut_ptr())
     }
 
     pub fn create_with_c_func(
@@ -112,36 +572,1096 @@ impl Thread {
         ))
         .map(|_| Thread {
             tx_struct: self.tx_struct,
+            name: self.name,
+            ..Thread::new()
         })
     }
 }
 
-impl ThreadHandle {
-    pub fn start(&mut self) -> Result<ThreadHandle, TxError> {
-        tx_checked_call!(_tx_thread_resume(self.tx_ptr))?;
-        Ok(ThreadHandle {
-            tx_ptr: self.tx_ptr,
+impl<S> ThreadHandle<S> {
+    fn new(tx_ptr: *mut TX_THREAD) -> Self {
+        ThreadHandle {
+            tx_ptr,
+            state: PhantomData,
+        }
+    }
+
+    fn into_state<N>(self) -> ThreadHandle<N> {
+        ThreadHandle::new(self.tx_ptr)
+    }
+
+    /// Identity of the thread, e.g. to compare it with the owner in `MutexInfo`.
+    pub fn id(&self) -> ThreadId {
+        ThreadId(self.tx_ptr as usize)
+    }
+
+    /// The Rust side data of the thread if it was created from a closure.
+    fn ext(&self) -> Option<&ThreadExt> {
+        // Safety: The control block stays valid as long as the thread is not deleted.
+        let (entry, arg) = unsafe {
+            (
+                (*self.tx_ptr).tx_thread_entry,
+                (*self.tx_ptr).tx_thread_entry_parameter,
+            )
+        };
+        let trampoline: unsafe extern "C" fn(ULONG) = thread_entry_trampoline;
+        match entry {
+            // Safety: Threads running the trampoline always get the address of the
+            // `ThreadExt` of their `Thread` as argument which lives for 'static.
+            Some(entry) if core::ptr::fn_addr_eq(entry, trampoline) => {
+                Some(unsafe { &*core::ptr::with_exposed_provenance(arg as usize) })
+            }
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn raw_state(&self) -> UINT {
+        // Safety: The control block stays valid as long as the thread is not deleted
+        // and the state is updated by the kernel behind our back hence the volatile read.
+        unsafe { core::ptr::read_volatile(&raw const (*self.tx_ptr).tx_thread_state) }
+    }
+
+    // A thread signals the end of its closure before the trampoline returns so
+    // ThreadX marks it completed slightly later. Wait for that before deleting it.
+    #[cfg(feature = "alloc")]
+    fn wait_completed(&self) -> Result<(), TxError> {
+        while !matches!(self.raw_state(), TX_COMPLETED | TX_TERMINATED) {
+            tx_checked_call!(_tx_thread_sleep(1))?;
+        }
+        Ok(())
+    }
+
+    /// Terminates the thread regardless of its current state. A terminated
+    /// thread can only be reset or deleted.
+    pub fn terminate(self) -> Result<ThreadHandle<Stopped>, TxError> {
+        tx_checked_call!(_tx_thread_terminate(self.tx_ptr))?;
+        Ok(self.into_state())
+    }
+
+    /// Query name, state, run count and scheduling parameters of the thread.
+    pub fn info(&self) -> Result<ThreadInfo<'_>, TxError> {
+        let mut name: *mut CHAR = core::ptr::null_mut();
+        let mut state: UINT = 0;
+        let mut run_count: ULONG = 0;
+        let mut priority: UINT = 0;
+        let mut preempt_threshold: UINT = 0;
+        let mut time_slice: ULONG = 0;
+        tx_checked_call!(_tx_thread_info_get(
+            self.tx_ptr,
+            &mut name,
+            &mut state,
+            &mut run_count,
+            &mut priority,
+            &mut preempt_threshold,
+            &mut time_slice,
+            core::ptr::null_mut(),
+            core::ptr::null_mut()
+        ))?;
+        Ok(ThreadInfo {
+            // Safety: The name is owned by the thread and outlives the handle.
+            name: (!name.is_null()).then(|| unsafe { CStr::from_ptr(name.cast()) }),
+            state: ThreadState::from_u32(state).ok_or(TxError::Unknown)?,
+            run_count: run_count as u32,
+            priority: Priority(priority),
+            preempt_threshold: PreemptThreshold(preempt_threshold),
+            time_slice: time_slice as u32,
         })
     }
-    pub fn suspend(&mut self) -> Result<ThreadHandle, TxError> {
+
+    /// Size of the stack of the thread in bytes.
+    pub fn stack_size(&self) -> usize {
+        // Safety: The control block stays valid as long as the thread is not deleted.
+        unsafe { (*self.tx_ptr).tx_thread_stack_size as usize }
+    }
+
+    /// Maximum number of stack bytes the thread used so far. ThreadX fills
+    /// the stack with a pattern when the thread is created (unless
+    /// `TX_DISABLE_STACK_FILLING` is defined) so the deepest point reached is
+    /// the first word from the bottom that no longer holds the pattern.
+    pub fn stack_high_water_mark(&self) -> usize {
+        // Safety: The stack belongs to the thread and stays valid as long as the
+        // thread is not deleted.
+        unsafe {
+            let (start, end) = stack_bounds(self.tx_ptr);
+            stack_used(start, end)
+        }
+    }
+
+    /// Change the priority of the thread and return the previous priority.
+    /// Note that ThreadX also sets the preemption threshold to the new priority.
+    pub fn change_priority(&self, priority: Priority) -> Result<Priority, TxError> {
+        let mut old_priority: UINT = 0;
+        tx_checked_call!(_tx_thread_priority_change(
+            self.tx_ptr,
+            priority.into(),
+            &mut old_priority
+        ))?;
+        Ok(Priority(old_priority))
+    }
+
+    /// Change the preemption threshold of the thread and return the previous
+    /// threshold. The threshold must not be lower than the priority of the thread.
+    pub fn change_preempt_threshold(
+        &self,
+        threshold: PreemptThreshold,
+    ) -> Result<PreemptThreshold, TxError> {
+        // Safety: The control block stays valid as long as the thread is not deleted.
+        let user_priority = unsafe { (*self.tx_ptr).tx_thread_user_priority };
+        if threshold.0 > user_priority {
+            return Err(TxError::ThreshError);
+        }
+        let mut old_threshold: UINT = 0;
+        tx_checked_call!(_tx_thread_preemption_change(
+            self.tx_ptr,
+            threshold.into(),
+            &mut old_threshold
+        ))?;
+        Ok(PreemptThreshold(old_threshold))
+    }
+
+    /// Change the time slice in ticks of the thread and return the previous
+    /// time slice. A time slice of 0 disables time slicing.
+    pub fn change_time_slice(&self, time_slice: u32) -> Result<u32, TxError> {
+        let mut old_time_slice: ULONG = 0;
+        tx_checked_call!(_tx_thread_time_slice_change(
+            self.tx_ptr,
+            time_slice as ULONG,
+            &mut old_time_slice
+        ))?;
+        Ok(old_time_slice as u32)
+    }
+
+    /// Abort a sleep or any other blocking call the thread is currently
+    /// suspended in. The aborted call returns `TxError::WaitAborted`.
+    pub fn wait_abort(&self) -> Result<(), TxError> {
+        tx_checked_call!(_tx_thread_wait_abort(self.tx_ptr))
+    }
+}
+
+impl ThreadHandle<Suspended> {
+    pub fn start(self) -> Result<ThreadHandle<Running>, TxError> {
+        tx_checked_call!(_tx_thread_resume(self.tx_ptr))?;
+        Ok(self.into_state())
+    }
+
+    /// Attach a closure that is called when the thread enters its entry
+    /// function and when it exits, either by returning or by being terminated.
+    /// The closure runs on the thread that enters or exits respectively on the
+    /// thread that terminates it, so it must not block for long. A typical use
+    /// is to signal a supervisor which then deletes or restarts the thread.
+    /// The exit notification can run while the entry notification of the same
+    /// thread is preempted, hence the closure must be `Fn` and `Sync`.
+    ///
+    /// Registering while the thread is suspended guarantees that the closure is
+    /// never replaced while it runs. Only threads created from a closure support
+    /// notifications, for other threads `TxError::ThreadError` is returned.
+    #[cfg(feature = "alloc")]
+    pub fn set_entry_exit_notify<F>(&self, notify: F) -> Result<(), TxError>
+    where
+        F: Fn(ThreadEvent) + Send + Sync + 'static,
+    {
+        let ext = self.ext().ok_or(TxError::ThreadError)?;
+        // Safety: The thread is suspended so the notification cannot run concurrently.
+        unsafe { *ext.entry_exit_notify.get() = Some(alloc::boxed::Box::new(notify)) };
+        tx_checked_call!(_tx_thread_entry_exit_notify(
+            self.tx_ptr,
+            Some(entry_exit_notify_trampoline)
+        ))
+    }
+
+    /// Remove a closure attached with `set_entry_exit_notify`.
+    #[cfg(feature = "alloc")]
+    pub fn clear_entry_exit_notify(&self) -> Result<(), TxError> {
+        tx_checked_call!(_tx_thread_entry_exit_notify(self.tx_ptr, None))?;
+        if let Some(ext) = self.ext() {
+            // Safety: The thread is suspended so the notification cannot run concurrently.
+            drop(unsafe { (*ext.entry_exit_notify.get()).take() });
+        }
+        Ok(())
+    }
+}
+
+impl ThreadHandle<Running> {
+    pub fn suspend(self) -> Result<ThreadHandle<Suspended>, TxError> {
         tx_checked_call!(_tx_thread_suspend(self.tx_ptr))?;
-        Ok(ThreadHandle {
-            tx_ptr: self.tx_ptr,
-        })
+        Ok(self.into_state())
+    }
+}
+
+impl ThreadHandle<Stopped> {
+    /// Reset a completed or terminated thread so it executes from its entry
+    /// point again once started.
+    pub fn reset(self) -> Result<ThreadHandle<Suspended>, TxError> {
+        tx_checked_call!(_tx_thread_reset(self.tx_ptr))?;
+        Ok(self.into_state())
     }
+
     /// Deletes the thread. You need to pass ownership
     /// of the thread handle to this function.
     pub fn delete(self) -> Result<(), TxError> {
-        tx_checked_call!(_tx_thread_delete(self.tx_ptr))
+        tx_checked_call!(_tx_thread_delete(self.tx_ptr))?;
+        if let Some(ext) = self.ext() {
+            // Safety: The thread is deleted so none of the closures can run anymore
+            // and nothing uses its stack.
+            unsafe {
+                if let Some(entry) = (*ext.entry.get()).take() {
+                    entry.as_ref().clear();
+                }
+                #[cfg(feature = "alloc")]
+                drop((*ext.entry_exit_notify.get()).take());
+                // Thread locals are only left if the thread was terminated. They may
+                // not be `Send`, so they are leaked instead of dropped on this thread.
+                #[cfg(feature = "alloc")]
+                core::mem::forget((*ext.locals.get()).take());
+            }
+            // Safety: See above.
+            if unsafe { core::mem::take(&mut *ext.pool_stack.get()) } {
+                let stack = unsafe { (*self.tx_ptr).tx_thread_stack_start };
+                tx_checked_call!(_tx_byte_release(stack))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum BuilderStack<'a> {
+    None,
+    Static(&'static mut [u8]),
+    Pool(&'a BytePoolHandle<'static>, usize),
+}
+
+/// Thread factory to configure a thread before creating it. The type
+/// parameter is the state of the created thread, threads are started right
+/// away unless `suspended` is used.
+///
+/// Defaults to no name, the lowest priority, no preemption threshold and
+/// no time slicing. A stack has to be provided.
+///
+/// ```ignore
+/// let handle = thread::Builder::new()
+///     .name("worker")
+///     .priority(4)
+///     .stack_from_pool(&pool, 2048)
+///     .spawn(thread, || do_work())?;
+/// ```
+pub struct Builder<'a, S = Running> {
+    name: &'a str,
+    priority: u32,
+    preempt_threshold: Option<u32>,
+    time_slice: u32,
+    stack: BuilderStack<'a>,
+    state: PhantomData<S>,
+}
+
+impl Builder<'_, Running> {
+    pub fn new() -> Self {
+        Builder {
+            name: "",
+            priority: TX_MAX_PRIORITIES - 1,
+            preempt_threshold: None,
+            time_slice: 0,
+            stack: BuilderStack::None,
+            state: PhantomData,
+        }
+    }
+}
+
+impl Default for Builder<'_, Running> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> Builder<'a, S> {
+    /// Name of the thread, at most 31 bytes long.
+    pub fn name(mut self, name: &'a str) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Defaults to the priority of the thread, which disables preemption threshold.
+    pub fn preempt_threshold(mut self, preempt_threshold: u32) -> Self {
+        self.preempt_threshold = Some(preempt_threshold);
+        self
+    }
+
+    /// Time slice in ticks, 0 disables time slicing.
+    pub fn time_slice(mut self, time_slice: u32) -> Self {
+        self.time_slice = time_slice;
+        self
+    }
+
+    pub fn stack(mut self, stack: &'static mut [u8]) -> Self {
+        self.stack = BuilderStack::Static(stack);
+        self
+    }
+
+    /// Allocate a stack of `size` bytes from `pool` when the thread is
+    /// created. The stack is released to the pool when the thread is deleted.
+    pub fn stack_from_pool(mut self, pool: &'a BytePoolHandle<'static>, size: usize) -> Self {
+        self.stack = BuilderStack::Pool(pool, size);
+        self
+    }
+
+    /// Start the thread when it is created.
+    pub fn auto_start(self) -> Builder<'a, Running> {
+        self.into_state()
+    }
+
+    /// Create the thread without starting it. Use `ThreadHandle::start` to
+    /// let the thread run.
+    pub fn suspended(self) -> Builder<'a, Suspended> {
+        self.into_state()
+    }
+
+    fn into_state<N>(self) -> Builder<'a, N> {
+        Builder {
+            name: self.name,
+            priority: self.priority,
+            preempt_threshold: self.preempt_threshold,
+            time_slice: self.time_slice,
+            stack: self.stack,
+            state: PhantomData,
+        }
+    }
+
+    fn create(
+        self,
+        thread: &mut Thread,
+        entry: NonNull<dyn Entry>,
+        auto_start: bool,
+    ) -> Result<*mut TX_THREAD, TxError> {
+        let (stack, pool) = match self.stack {
+            BuilderStack::None => return Err(TxError::SizeError),
+            BuilderStack::Static(stack) => (stack, None),
+            BuilderStack::Pool(pool, size) => {
+                (pool.allocate(size, WaitOption::NoWait)?.consume(), Some(pool))
+            }
+        };
+        *thread.ext.pool_stack.get_mut() = pool.is_some();
+        let res = thread.create(
+            self.name,
+            entry,
+            stack,
+            self.priority,
+            self.preempt_threshold.unwrap_or(self.priority),
+            self.time_slice,
+            auto_start,
+        );
+        if res.is_err() {
+            *thread.ext.pool_stack.get_mut() = false;
+            if let Some(pool) = pool {
+                pool.release(stack)?;
+            }
+        }
+        res
+    }
+
+    #[cfg(feature = "alloc")]
+    fn create_box(
+        self,
+        thread: &'static mut Thread,
+        entry_function: alloc::boxed::Box<dyn FnOnce()>,
+        auto_start: bool,
+    ) -> Result<*mut TX_THREAD, TxError> {
+        *thread.boxed_entry.get_mut() = Some(entry_function);
+        let entry = NonNull::from(&thread.boxed_entry);
+        self.create(thread, entry, auto_start)
+    }
+
+    fn create_static<F, const SIZE: usize>(
+        self,
+        thread: &'static mut StaticThread<SIZE>,
+        f: F,
+        auto_start: bool,
+    ) -> Result<*mut TX_THREAD, TxError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        thread.entry.set(f);
+        let entry = NonNull::from(&thread.entry);
+        self.create(&mut thread.thread, entry, auto_start)
     }
 }
 
+impl Builder<'_, Running> {
+    /// Create and start a thread running `f`.
+    #[cfg(feature = "alloc")]
+    pub fn spawn<F>(
+        self,
+        thread: &'static mut Thread,
+        f: F,
+    ) -> Result<ThreadHandle<Running>, TxError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.create_box(thread, alloc::boxed::Box::new(f), true)
+            .map(ThreadHandle::new)
+    }
+
+    /// Create and start a thread running `f` which is stored inline in `thread`.
+    pub fn spawn_static<F, const SIZE: usize>(
+        self,
+        thread: &'static mut StaticThread<SIZE>,
+        f: F,
+    ) -> Result<ThreadHandle<Running>, TxError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.create_static(thread, f, true).map(ThreadHandle::new)
+    }
+
+    /// Create and start a thread whose return value can be retrieved with
+    /// the returned `JoinHandle`.
+    #[cfg(feature = "alloc")]
+    pub fn spawn_joinable<F, T>(
+        self,
+        thread: &'static mut Thread,
+        f: F,
+    ) -> Result<JoinHandle<T>, TxError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let packet = Packet::new()?;
+        let their_packet = packet.clone();
+        let main = alloc::boxed::Box::new(move || their_packet.complete(f()));
+        let thread = self.create_box(thread, main, true).map(ThreadHandle::new)?;
+        Ok(JoinHandle { thread, packet })
+    }
+}
+
+impl Builder<'_, Suspended> {
+    /// Create a thread running `f` without starting it.
+    #[cfg(feature = "alloc")]
+    pub fn spawn<F>(
+        self,
+        thread: &'static mut Thread,
+        f: F,
+    ) -> Result<ThreadHandle<Suspended>, TxError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.create_box(thread, alloc::boxed::Box::new(f), false)
+            .map(ThreadHandle::new)
+    }
+
+    /// Create a thread running `f`, which is stored inline in `thread`, without starting it.
+    pub fn spawn_static<F, const SIZE: usize>(
+        self,
+        thread: &'static mut StaticThread<SIZE>,
+        f: F,
+    ) -> Result<ThreadHandle<Suspended>, TxError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.create_static(thread, f, false).map(ThreadHandle::new)
+    }
+}
+
+#[cfg(feature = "alloc")]
+const PACKET_DONE: ULONG = 0x1;
+
+/// Shared between a joinable thread and its `JoinHandle`. The event flags
+/// group lives inside the `Arc` allocation so it is never moved after it
+/// was created.
+#[cfg(feature = "alloc")]
+struct Packet<T> {
+    result: UnsafeCell<Option<T>>,
+    done: UnsafeCell<MaybeUninit<TX_EVENT_FLAGS_GROUP>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Packet<T> {
+    fn new() -> Result<alloc::sync::Arc<Packet<T>>, TxError> {
+        let packet = alloc::sync::Arc::new(Packet {
+            result: UnsafeCell::new(None),
+            done: UnsafeCell::new(MaybeUninit::uninit()),
+        });
+        tx_checked_call!(_tx_event_flags_create(
+            packet.done_ptr(),
+            c"join".as_ptr() as *mut i8
+        ))?;
+        Ok(packet)
+    }
+
+    fn done_ptr(&self) -> *mut TX_EVENT_FLAGS_GROUP {
+        // Safety: The pointer is only handed to the ThreadX event flag services.
+        unsafe { (*self.done.get()).as_mut_ptr() }
+    }
+
+    fn complete(&self, result: T) {
+        // Safety: Only the joinable thread writes the result and it does so before
+        // setting the flag. The `JoinHandle` reads it only after observing the flag.
+        unsafe { *self.result.get() = Some(result) };
+        if tx_checked_call!(_tx_event_flags_set(self.done_ptr(), PACKET_DONE, TX_OR)).is_err() {
+            error!("Failed to signal thread completion");
+        }
+    }
+
+    fn wait(&self, wait_option: WaitOption) -> Result<(), TxError> {
+        let mut actual_flags: ULONG = 0;
+        // The flag is not cleared so waiting again returns immediately.
+        tx_checked_call!(_tx_event_flags_get(
+            self.done_ptr(),
+            PACKET_DONE,
+            TX_AND,
+            &mut actual_flags,
+            wait_option.into()
+        ))
+        .map_err(|e| wait_option.timed_out(e))
+    }
+
+    /// Poll the flag without logging, a running thread is not an error.
+    fn is_done(&self) -> bool {
+        let mut actual_flags: ULONG = 0;
+        tx_checked_call_no_log!(_tx_event_flags_get(
+            self.done_ptr(),
+            PACKET_DONE,
+            TX_AND,
+            &mut actual_flags,
+            WaitOption::NoWait.into()
+        ))
+        .is_ok()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Drop for Packet<T> {
+    fn drop(&mut self) {
+        let _ = tx_checked_call!(_tx_event_flags_delete(self.done_ptr()));
+    }
+}
+
+/// Owned permission to join a thread created with `Thread::initialize_joinable`.
+/// Dropping the handle detaches the thread.
+#[cfg(feature = "alloc")]
+pub struct JoinHandle<T> {
+    thread: ThreadHandle<Running>,
+    packet: alloc::sync::Arc<Packet<T>>,
+}
+
+/// Safety: The result is only moved out of the packet after the thread
+/// producing it signalled completion.
+#[cfg(feature = "alloc")]
+unsafe impl<T: Send> Send for JoinHandle<T> {}
+#[cfg(feature = "alloc")]
+unsafe impl<T: Send> Sync for JoinHandle<T> {}
+
+#[cfg(feature = "alloc")]
+impl<T> JoinHandle<T> {
+    pub fn thread(&self) -> &ThreadHandle<Running> {
+        &self.thread
+    }
+
+    /// Returns true if the closure of the thread has returned.
+    pub fn is_finished(&self) -> bool {
+        self.packet.is_done()
+    }
+
+    /// Block the calling thread until the joined thread has returned from its
+    /// closure or the wait option expired. Can be called more than once.
+    pub fn wait(&self, wait_option: WaitOption) -> Result<(), TxError> {
+        self.packet.wait(wait_option)
+    }
+
+    /// Wait for the thread to finish and return the value produced by its
+    /// closure. The completed thread is deleted afterwards.
+    pub fn join(self) -> Result<T, TxError> {
+        self.packet.wait(WaitOption::WaitForever)?;
+        self.thread.wait_completed()?;
+        // Safety: The flag was observed so the thread wrote the result and will not touch it again.
+        let result = unsafe { (*self.packet.result.get()).take() };
+        self.thread.into_state::<Stopped>().delete()?;
+        result.ok_or(TxError::NoInstance)
+    }
+}
+
+// Lets a scope wait for threads regardless of the type they return.
+#[cfg(feature = "alloc")]
+trait Completion {
+    fn wait(&self, wait_option: WaitOption) -> Result<(), TxError>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Completion for Packet<T> {
+    fn wait(&self, wait_option: WaitOption) -> Result<(), TxError> {
+        Packet::wait(self, wait_option)
+    }
+}
+
+/// Scope to spawn threads that may borrow data from the spawning thread. See `scope`.
+#[cfg(feature = "alloc")]
+pub struct Scope<'scope, 'env: 'scope> {
+    // Intrusive list of all threads spawned in the scope, guarded by a critical section.
+    threads: UnsafeCell<*mut ScopedThread<'scope>>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+/// Safety: The thread list is only accessed within a critical section.
+#[cfg(feature = "alloc")]
+unsafe impl Sync for Scope<'_, '_> {}
+
+#[cfg(feature = "alloc")]
+struct ScopedThread<'scope> {
+    thread: Thread,
+    completion: alloc::sync::Arc<dyn Completion + 'scope>,
+    next: *mut ScopedThread<'scope>,
+}
+
+/// Owned permission to join a thread spawned in a scope.
+#[cfg(feature = "alloc")]
+pub struct ScopedJoinHandle<'scope, T> {
+    thread: ThreadHandle<Running>,
+    packet: alloc::sync::Arc<Packet<T>>,
+    scope: PhantomData<&'scope ()>,
+}
+
+/// Safety: The result is only moved out of the packet after the thread
+/// producing it signalled completion.
+#[cfg(feature = "alloc")]
+unsafe impl<T: Send> Send for ScopedJoinHandle<'_, T> {}
+#[cfg(feature = "alloc")]
+unsafe impl<T: Send> Sync for ScopedJoinHandle<'_, T> {}
+
+/// Create a scope for spawning threads that may borrow non-`'static` data,
+/// including their stacks. All threads spawned in the scope are waited for
+/// and deleted before this function returns, whether they were joined or not.
+///
+/// ```ignore
+/// let mut counter = 0;
+/// let stack = pool.allocate(1024, WaitForever)?.consume();
+/// thread::scope(|s| {
+///     s.spawn("worker", &mut *stack, 4, 4, 0, || counter += 1).unwrap();
+/// });
+/// pool.release(stack)?;
+/// ```
+#[cfg(feature = "alloc")]
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+{
+    let scope = Scope {
+        threads: UnsafeCell::new(core::ptr::null_mut()),
+        scope: PhantomData,
+        env: PhantomData,
+    };
+    let result = f(&scope);
+    scope.join_all();
+    result
+}
+
+#[cfg(feature = "alloc")]
+impl<'scope> Scope<'scope, '_> {
+    /// Spawn a thread within the scope. The closure and the stack only need to
+    /// live as long as the scope.
+    pub fn spawn<F, T>(
+        &'scope self,
+        name: &str,
+        stack: &'scope mut [u8],
+        priority: u32,
+        preempt_threshold: u32,
+        time_slice: u32,
+        f: F,
+    ) -> Result<ScopedJoinHandle<'scope, T>, TxError>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let packet = Packet::new()?;
+        let their_packet = packet.clone();
+        let main: alloc::boxed::Box<dyn FnOnce() + 'scope> =
+            alloc::boxed::Box::new(move || their_packet.complete(f()));
+        // Safety: The scope waits for all of its threads to finish before 'scope ends.
+        let main: alloc::boxed::Box<dyn FnOnce()> = unsafe { core::mem::transmute(main) };
+
+        // The control block is boxed so it does not move while the thread exists.
+        let mut node = alloc::boxed::Box::new(ScopedThread {
+            thread: Thread::new(),
+            completion: packet.clone(),
+            next: core::ptr::null_mut(),
+        });
+        let tx_ptr = node.thread.create_box(
+            name,
+            main,
+            stack,
+            priority,
+            preempt_threshold,
+            time_slice,
+            true,
+        )?;
+        let node = alloc::boxed::Box::into_raw(node);
+        critical_section(|| unsafe {
+            (*node).next = *self.threads.get();
+            *self.threads.get() = node;
+        });
+        Ok(ScopedJoinHandle {
+            thread: ThreadHandle::new(tx_ptr),
+            packet,
+            scope: PhantomData,
+        })
+    }
+
+    // Threads may spawn further threads into the scope before they finish, so
+    // keep taking threads off the list until it is empty.
+    fn join_all(&self) {
+        loop {
+            let node = critical_section(|| unsafe {
+                let head = *self.threads.get();
+                if !head.is_null() {
+                    *self.threads.get() = (*head).next;
+                }
+                head
+            });
+            if node.is_null() {
+                break;
+            }
+            // Safety: The node was leaked by `spawn` and is only taken off the list once.
+            let mut node = unsafe { alloc::boxed::Box::from_raw(node) };
+            let thread: ThreadHandle<Running> =
+                ThreadHandle::new(node.thread.tx_struct.as_mut_ptr());
+            let res = node
+                .completion
+                .wait(WaitOption::WaitForever)
+                .and_then(|_| thread.wait_completed())
+                .and_then(|_| thread.into_state::<Stopped>().delete());
+            if res.is_err() {
+                // Leak the control block since ThreadX may still reference it.
+                error!("Failed to delete scoped thread");
+                core::mem::forget(node);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> ScopedJoinHandle<'_, T> {
+    pub fn thread(&self) -> &ThreadHandle<Running> {
+        &self.thread
+    }
+
+    /// Returns true if the closure of the thread has returned.
+    pub fn is_finished(&self) -> bool {
+        self.packet.is_done()
+    }
+
+    /// Wait for the thread to finish and return the value produced by its
+    /// closure. The thread is deleted when the scope ends.
+    pub fn join(self) -> Result<T, TxError> {
+        self.packet.wait(WaitOption::WaitForever)?;
+        // Safety: The flag was observed so the thread wrote the result and will not touch it again.
+        unsafe { (*self.packet.result.get()).take() }.ok_or(TxError::NoInstance)
+    }
+}
+
+/// Declare thread local variables. Every thread gets its own value which is
+/// created by the initializer on first access and dropped by the thread itself
+/// when its entry function returns. The values of a thread that was terminated
+/// are leaked when it is deleted, since dropping them would run the destructors
+/// on the deleting thread.
+///
+/// ```ignore
+/// threadx_rs::thread_local! {
+///     static COUNTER: Cell<u32> = Cell::new(0);
+/// }
+/// COUNTER.with(|counter| counter.set(counter.get() + 1));
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! thread_local {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr; $($rest:tt)*) => {
+        $crate::thread_local!($(#[$attr])* $vis static $name: $t = $init);
+        $crate::thread_local!($($rest)*);
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr) => {
+        $(#[$attr])*
+        $vis static $name: $crate::thread::LocalKey<$t> = $crate::thread::LocalKey::new({
+            fn __init() -> $t {
+                $init
+            }
+            __init
+        });
+    };
+}
+
+/// Key of a thread local variable declared with `thread_local!`.
+#[cfg(feature = "alloc")]
+pub struct LocalKey<T: 'static> {
+    init: fn() -> T,
+}
+
+#[cfg(feature = "alloc")]
+struct LocalNode {
+    key: *const (),
+    value: alloc::boxed::Box<dyn core::any::Any>,
+    next: Option<alloc::boxed::Box<LocalNode>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new(init: fn() -> T) -> LocalKey<T> {
+        LocalKey { init }
+    }
+
+    /// Call `f` with the value of the current thread, creating it first if
+    /// needed. Fails with `TxError::CallerError` when not called from a thread
+    /// and with `TxError::ThreadError` for threads not created from a closure.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, TxError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let thread = current().ok_or(TxError::CallerError)?;
+        let ext = thread.ext().ok_or(TxError::ThreadError)?;
+        let key: *const () = (self as *const Self).cast();
+        // Safety: Only the thread itself accesses its locals while it runs. The
+        // values are boxed and only dropped once the thread has exited, so they
+        // stay in place while `f` runs even if further locals are created.
+        let value = unsafe {
+            let mut node = &*ext.locals.get();
+            while let Some(n) = node {
+                if n.key == key {
+                    break;
+                }
+                node = &n.next;
+            }
+            match node {
+                Some(n) => &*n.value as *const dyn core::any::Any,
+                None => {
+                    // The initializer may access other thread locals so it runs
+                    // before the list is touched.
+                    let value: alloc::boxed::Box<dyn core::any::Any> =
+                        alloc::boxed::Box::new((self.init)());
+                    let value_ptr = &*value as *const dyn core::any::Any;
+                    let next = (*ext.locals.get()).take();
+                    *ext.locals.get() =
+                        Some(alloc::boxed::Box::new(LocalNode { key, value, next }));
+                    value_ptr
+                }
+            }
+        };
+        // Safety: See above.
+        let value = unsafe { &*value };
+        Ok(f(value.downcast_ref::<T>().ok_or(TxError::Unknown)?))
+    }
+
+    /// Like `try_with` but panics if the value cannot be accessed.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.try_with(f)
+            .expect("thread local accessed outside of a closure thread")
+    }
+}
+
+/// Borrowed handle to the thread that is currently executing. Only the
+/// methods taking `&self` are available so the owner of the thread handle
+/// keeps track of the thread state.
+pub struct CurrentThread {
+    handle: ThreadHandle<Running>,
+    // Only meaningful on the thread that obtained it.
+    not_send: PhantomData<*const ()>,
+}
+
+impl Deref for CurrentThread {
+    type Target = ThreadHandle<Running>;
+
+    fn deref(&self) -> &ThreadHandle<Running> {
+        &self.handle
+    }
+}
+
+/// Identity of a thread as reported by the info of other kernel objects. It
+/// only tells threads apart and gives no access to the thread, which may have
+/// been deleted in the meantime. A thread created later in the same control
+/// block gets the same id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ThreadId(usize);
+
+impl ThreadId {
+    pub(crate) fn from_ptr(tx_ptr: *mut TX_THREAD) -> Option<ThreadId> {
+        (!tx_ptr.is_null()).then_some(ThreadId(tx_ptr as usize))
+    }
+
+    /// Returns true if this is the thread that is currently executing.
+    pub fn is_current(self) -> bool {
+        self.0 == unsafe { _tx_thread_identify() } as usize
+    }
+}
+
+/// Returns the thread that is currently executing or `None` when called from
+/// initialization or interrupt context.
+pub fn current() -> Option<CurrentThread> {
+    let tx_ptr = unsafe { _tx_thread_identify() };
+    (!tx_ptr.is_null()).then(|| CurrentThread {
+        handle: ThreadHandle::new(tx_ptr),
+        not_send: PhantomData,
+    })
+}
+
+/// Pattern ThreadX writes to the stack of a thread on creation.
+const TX_STACK_FILL: u32 = 0xEFEF_EFEF;
+
+/// Called by ThreadX with the thread whose stack overflowed. The callback
+/// runs in the context of the scheduler so it must not block.
+pub type StackErrorCb = fn(&ThreadHandle<Running>);
+
+static STACK_ERROR_CB: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+unsafe extern "C" fn stack_error_trampoline(thread_ptr: *mut TX_THREAD) {
+    let cb = STACK_ERROR_CB.load(Ordering::Acquire);
+    if !cb.is_null() {
+        // Safety: Only `StackErrorCb` function pointers are stored in STACK_ERROR_CB.
+        let cb: StackErrorCb = core::mem::transmute(cb);
+        cb(&ThreadHandle::new(thread_ptr));
+    }
+}
+
+/// Register a callback that is called when ThreadX detects a stack overflow.
+/// ThreadX must be built with `TX_ENABLE_STACK_CHECKING`, otherwise
+/// `TxError::FeatureNotEnabled` is returned.
+pub fn set_stack_error_handler(handler: StackErrorCb) -> Result<(), TxError> {
+    STACK_ERROR_CB.store(handler as *mut (), Ordering::Release);
+    tx_checked_call!(_tx_thread_stack_error_notify(Some(stack_error_trampoline)))
+}
+
+// List of all created threads maintained by ThreadX.
+extern "C" {
+    static _tx_thread_created_ptr: *mut TX_THREAD;
+    static _tx_thread_created_count: ULONG;
+}
+
+/// Number of threads reported by `log_stack_usage`.
+const MAX_REPORTED_THREADS: usize = 16;
+
+#[derive(Clone, Copy)]
+struct StackUsage {
+    // Truncated copy, the name may be freed together with the thread
+    name: [u8; 16],
+    start: *const u8,
+    end: *const u8,
+}
+
+/// First and one past the last byte of the stack of a thread.
+///
+/// Safety: `thread_ptr` must point to a created thread.
+unsafe fn stack_bounds(thread_ptr: *mut TX_THREAD) -> (*const u8, *const u8) {
+    let start = (*thread_ptr).tx_thread_stack_start as *const u8;
+    // The stack end is the address of the last byte of the stack.
+    let end = ((*thread_ptr).tx_thread_stack_end as *const u8).add(1);
+    (start, end)
+}
+
+/// Number of bytes of the stack `start..end` used so far. ThreadX fills the
+/// stack with a pattern when the thread is created, so the deepest point
+/// reached is the first word from the bottom that no longer holds the pattern.
+///
+/// Safety: `start..end` must be readable. The thread may write to it
+/// concurrently, hence the volatile reads.
+unsafe fn stack_used(start: *const u8, end: *const u8) -> usize {
+    let mut word = start.add(start.align_offset(size_of::<u32>())) as *const u32;
+    while (word as *const u8) < end && core::ptr::read_volatile(word) == TX_STACK_FILL {
+        word = word.add(1);
+    }
+    (end as usize).saturating_sub(word as usize)
+}
+
+/// Log the stack usage of the first 16 created threads. Useful to size
+/// thread stacks. Only the stack bounds and names are collected with
+/// interrupts disabled, so threads created or deleted concurrently do not
+/// disturb the walk of the thread list. The stacks are scanned and logged
+/// afterwards, a thread deleted in the meantime may be reported with wrong
+/// figures.
+pub fn log_stack_usage() {
+    let empty = StackUsage {
+        name: [0; 16],
+        start: core::ptr::null(),
+        end: core::ptr::null(),
+    };
+    let mut usage = [empty; MAX_REPORTED_THREADS];
+    let (count, total) = critical_section(|| {
+        // Safety: The created list is circular and contains `_tx_thread_created_count`
+        // entries. It cannot change while interrupts are disabled.
+        let (mut thread_ptr, total) = unsafe { (_tx_thread_created_ptr, _tx_thread_created_count) };
+        let count = (total as usize).min(MAX_REPORTED_THREADS);
+        for entry in &mut usage[..count] {
+            let name = unsafe { (*thread_ptr).tx_thread_name } as *const u8;
+            if !name.is_null() {
+                // Bounded copy, the name is not scanned to its end
+                for (i, byte) in entry.name.iter_mut().enumerate() {
+                    match unsafe { name.add(i).read() } {
+                        0 => break,
+                        b => *byte = b,
+                    }
+                }
+            }
+            (entry.start, entry.end) = unsafe { stack_bounds(thread_ptr) };
+            thread_ptr = unsafe { (*thread_ptr).tx_thread_created_next };
+        }
+        (count, total as usize)
+    });
+    for entry in &usage[..count] {
+        let size = (entry.end as usize).saturating_sub(entry.start as usize);
+        // Safety: Stacks are plain memory which stays readable even if the
+        // thread was deleted in the meantime.
+        let used = unsafe { stack_used(entry.start, entry.end) };
+        let len = entry.name.iter().position(|&b| b == 0).unwrap_or(entry.name.len());
+        let name = match core::str::from_utf8(&entry.name[..len]) {
+            Ok("") => "<unnamed>",
+            Ok(name) => name,
+            // Cut within a character
+            Err(e) => core::str::from_utf8(&entry.name[..e.valid_up_to()]).unwrap_or_default(),
+        };
+        defmt::info!(
+            "Thread {}: stack used {} of {} bytes, headroom {} bytes",
+            name,
+            used,
+            size,
+            size.saturating_sub(used)
+        );
+    }
+    if total > count {
+        defmt::info!("{} more threads not shown", total - count);
+    }
+}
+
+/// Give up the processor to other ready threads of the same or higher priority.
+pub fn relinquish() {
+    unsafe { _tx_thread_relinquish() }
+}
+
 /// Put the current task to sleep for the specified duration. Note that
 /// the minimum sleep time is 1 os tick and the wall time that represents
 /// will be rounded up to the nearest tick.  So if the os tick is 10ms,
 /// which is the default, and you sleep for 1ms, you will actually sleep
 /// for 10ms. The number of ticks per second is a compile time constant
-/// available at `threadx-sys::TX_TICKS_PER_SECOND`
+/// available at `threadx-sys::TX_TICKS_PER_SECOND`. Since the current tick is
+/// already partly over, the sleep may end up to one tick before `d` passed.
 pub fn sleep(d: Duration) -> Result<(), TxError> {
-    tx_checked_call!(_tx_thread_sleep(TxTicks::from(d).into()))
+    sleep_ticks(TxTicks::from(d))
+}
+
+/// Put the current thread to sleep for `ticks` kernel ticks. The current
+/// tick is already partly over, so the sleep may end up to one tick early.
+pub(crate) fn sleep_ticks(ticks: TxTicks) -> Result<(), TxError> {
+    tx_checked_call!(_tx_thread_sleep(ticks.into()))
+}
+
+/// Put the current thread to sleep until `deadline`. Returns immediately if
+/// the deadline already passed.
+pub fn sleep_until(deadline: Instant) -> Result<(), TxError> {
+    let remaining = deadline.ticks_since(Instant::now());
+    if remaining <= 0 {
+        return Ok(());
+    }
+    tx_checked_call!(_tx_thread_sleep(remaining as ULONG))
 }
@@ -1,23 +1,285 @@
+use core::cmp::Ordering;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
 use core::time::Duration;
 
-use threadx_sys::TX_TIMER_TICKS_PER_SECOND;
+use threadx_sys::{_tx_time_get, _tx_time_set, TX_TIMER_TICKS_PER_SECOND};
 
-pub struct TxTicks(u32);
+use crate::critical_section;
+use crate::error::TxError;
+use crate::thread::sleep_until;
 
 /// `threadx_sys::TX_TIMER_TICKS_PER_SECOND` is a constant that is set by the
 /// ThreadX build configuration. The default is 100 and it can be
 /// changed by providing a user defined `tx_user.h` file.
-const MILLIS_PER_TICK: u128 = 1000 / TX_TIMER_TICKS_PER_SECOND as u128;
+pub const TICKS_PER_SECOND: u32 = TX_TIMER_TICKS_PER_SECOND;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// How a `Duration` that is not a whole number of ticks is converted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round to the next tick, so the ticks cover at least the duration.
+    Up,
+    /// Round to the previous tick.
+    Down,
+}
+
+/// Number of kernel ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TxTicks(u32);
+
+impl TxTicks {
+    pub const ZERO: TxTicks = TxTicks(0);
+    pub const MAX: TxTicks = TxTicks(u32::MAX);
+
+    pub const fn new(ticks: u32) -> TxTicks {
+        TxTicks(ticks)
+    }
+
+    pub const fn ticks(self) -> u32 {
+        self.0
+    }
+
+    /// Convert a duration to ticks, `None` if it does not fit into 32 bits.
+    pub const fn checked_from_duration(d: Duration, rounding: Rounding) -> Option<TxTicks> {
+        let scaled = d.as_nanos() * TICKS_PER_SECOND as u128;
+        let ticks = match rounding {
+            Rounding::Up => scaled.div_ceil(NANOS_PER_SECOND),
+            Rounding::Down => scaled / NANOS_PER_SECOND,
+        };
+        if ticks > u32::MAX as u128 {
+            None
+        } else {
+            Some(TxTicks(ticks as u32))
+        }
+    }
+
+    /// Convert a duration to ticks, clamped to `TxTicks::MAX`.
+    pub const fn saturating_from_duration(d: Duration, rounding: Rounding) -> TxTicks {
+        match TxTicks::checked_from_duration(d, rounding) {
+            Some(ticks) => ticks,
+            None => TxTicks::MAX,
+        }
+    }
 
+    pub const fn to_duration(self) -> Duration {
+        let nanos = self.0 as u128 * NANOS_PER_SECOND / TICKS_PER_SECOND as u128;
+        Duration::new(
+            (nanos / NANOS_PER_SECOND) as u64,
+            (nanos % NANOS_PER_SECOND) as u32,
+        )
+    }
+}
+
+/// Rounds up and saturates. A sleep or timeout of that many ticks starts
+/// partway into the current tick, so it may still end up to one tick before
+/// the duration passed.
 impl From<Duration> for TxTicks {
     fn from(d: Duration) -> Self {
-        TxTicks((d.as_millis() / MILLIS_PER_TICK) as u32)
+        TxTicks::saturating_from_duration(d, Rounding::Up)
+    }
+}
+
+impl From<TxTicks> for Duration {
+    fn from(ticks: TxTicks) -> Duration {
+        ticks.to_duration()
+    }
+}
+
+impl From<TxTicks> for u32 {
+    fn from(ticks: TxTicks) -> u32 {
+        ticks.0
     }
 }
 
-impl Into<u32> for TxTicks {
-    fn into(self) -> u32 {
+/// Point in time as read from the ThreadX tick counter. The counter wraps
+/// around after 2^32 ticks so instants are compared relative to each other
+/// and are only meaningful within half of that range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instant(u32);
+
+impl Instant {
+    pub fn now() -> Instant {
+        Instant(unsafe { _tx_time_get() })
+    }
+
+    pub const fn from_ticks(ticks: u32) -> Instant {
+        Instant(ticks)
+    }
+
+    pub const fn ticks(self) -> u32 {
         self.0
     }
+
+    /// Number of ticks from `earlier` to `self`, negative if `self` is
+    /// actually before `earlier`.
+    pub const fn ticks_since(self, earlier: Instant) -> i32 {
+        self.0.wrapping_sub(earlier.0) as i32
+    }
+
+    pub const fn wrapping_add_ticks(self, ticks: u32) -> Instant {
+        Instant(self.0.wrapping_add(ticks))
+    }
+
+    /// Time from `earlier` to `self`, `None` if `earlier` is after `self`.
+    pub fn checked_duration_since(self, earlier: Instant) -> Option<Duration> {
+        u32::try_from(self.ticks_since(earlier))
+            .ok()
+            .map(|ticks| TxTicks(ticks).to_duration())
+    }
+
+    /// Time from `earlier` to `self`, zero if `earlier` is after `self`.
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    pub fn elapsed(self) -> Duration {
+        Instant::now().duration_since(self)
+    }
+
+    /// `None` if the result is too far away to still be compared with `self`.
+    pub fn checked_add(self, d: Duration) -> Option<Instant> {
+        let ticks = TxTicks::checked_from_duration(d, Rounding::Up)?;
+        (ticks.0 <= i32::MAX as u32).then(|| self.wrapping_add_ticks(ticks.0))
+    }
+
+    /// `None` if the result is too far away to still be compared with `self`.
+    pub fn checked_sub(self, d: Duration) -> Option<Instant> {
+        let ticks = TxTicks::checked_from_duration(d, Rounding::Up)?;
+        (ticks.0 <= i32::MAX as u32).then(|| Instant(self.0.wrapping_sub(ticks.0)))
+    }
+}
+
+/// Instants are ordered by their distance on the wrapping tick counter, so
+/// the order is only consistent for instants less than 2^31 ticks apart.
+impl PartialOrd for Instant {
+    fn partial_cmp(&self, other: &Instant) -> Option<Ordering> {
+        Some(self.ticks_since(*other).cmp(&0))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, d: Duration) -> Instant {
+        self.checked_add(d)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, d: Duration) {
+        *self = *self + d;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, d: Duration) -> Instant {
+        self.checked_sub(d)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, d: Duration) {
+        *self = *self - d;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// Set the kernel tick counter. Instants taken before are not adjusted and
+/// neither are sleeps and timeouts in progress, which count ticks instead.
+pub fn set_time(now: Instant) {
+    critical_section(|| {
+        unsafe { _tx_time_set(now.0) };
+        LAST_TICKS.store(now.0, AtomicOrdering::Relaxed);
+    })
+}
+
+// Tick count seen by the last call of `extended_ticks` and the number of
+// times the counter wrapped, only accessed within a critical section.
+static LAST_TICKS: AtomicU32 = AtomicU32::new(0);
+static WRAPS: AtomicU32 = AtomicU32::new(0);
+
+/// Kernel tick count extended to 64 bits. A wrap of the 32 bit counter is
+/// only noticed if this is called at least once every 2^32 ticks.
+pub fn extended_ticks() -> u64 {
+    critical_section(|| {
+        let ticks = unsafe { _tx_time_get() };
+        let mut wraps = WRAPS.load(AtomicOrdering::Relaxed);
+        if ticks < LAST_TICKS.load(AtomicOrdering::Relaxed) {
+            wraps = wraps.wrapping_add(1);
+            WRAPS.store(wraps, AtomicOrdering::Relaxed);
+        }
+        LAST_TICKS.store(ticks, AtomicOrdering::Relaxed);
+        (wraps as u64) << 32 | ticks as u64
+    })
 }
 
+/// Wakes the calling thread at a fixed cadence. Unlike sleeping for the
+/// period after each iteration the execution time of the loop does not
+/// add up over time.
+///
+/// ```ignore
+/// let mut ticker = Ticker::new(Duration::from_secs(5));
+/// loop {
+///     measure();
+///     if ticker.wait()? > 0 {
+///         defmt::warn!("Measurement overran its period");
+///     }
+/// }
+/// ```
+pub struct Ticker {
+    next: Instant,
+    period: u32,
+}
+
+impl Ticker {
+    /// Ticker that first expires one period from now. The period is at
+    /// least one tick.
+    pub fn new(period: Duration) -> Ticker {
+        let period: u32 = TxTicks::from(period).into();
+        let period = period.max(1);
+        Ticker {
+            next: Instant::now().wrapping_add_ticks(period),
+            period,
+        }
+    }
+
+    /// Sleep until the next period starts. If the period already started the
+    /// call returns immediately and periods that passed completely in the
+    /// meantime are skipped. Returns the number of period starts that passed
+    /// before the call, so anything but 0 indicates an overrun.
+    pub fn wait(&mut self) -> Result<u32, TxError> {
+        let late = Instant::now().ticks_since(self.next);
+        if late <= 0 {
+            sleep_until(self.next)?;
+            self.next = self.next.wrapping_add_ticks(self.period);
+            return Ok(0);
+        }
+        Ok(self.advance(late as u32))
+    }
+
+    /// Move the next period start past a point `late` ticks after it and
+    /// return the number of period starts passed.
+    fn advance(&mut self, late: u32) -> u32 {
+        let missed = late / self.period + 1;
+        self.next = self.next.wrapping_add_ticks(missed.wrapping_mul(self.period));
+        missed
+    }
+
+    /// Restart the cadence so the next period starts one period from now.
+    pub fn reset(&mut self) {
+        self.next = Instant::now().wrapping_add_ticks(self.period);
+    }
+}
@@ -21,6 +21,7 @@ use crate::time::TxTicks;
 use core::ffi::CStr;
 
 use super::error::TxError;
+#[cfg(feature = "alloc")]
 use defmt::println;
 use num_traits::FromPrimitive;
 use threadx_sys::_tx_timer_create;
@@ -30,9 +31,11 @@ use threadx_sys::ULONG;
 use core::mem::MaybeUninit;
 use threadx_sys::TX_TIMER;
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
 // arg will point to the wide pointer of a dyn Fn()
+#[cfg(feature = "alloc")]
 unsafe extern "C" fn timer_callback_trampoline(arg: ULONG) {
     let argc: *mut alloc::boxed::Box<dyn Fn()> = core::ptr::with_exposed_provenance_mut(arg as usize);
     (*argc)();
@@ -45,6 +48,7 @@ impl Timer {
         Timer(MaybeUninit::uninit())
     }
     /// Using a closure we need the ULONG arg t_expiration_inpu to trampoline so you cannot use it directly
+    #[cfg(feature = "alloc")]
     pub fn initialize_with_closure(
         &'static mut self,
         name: &CStr,